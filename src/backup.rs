@@ -0,0 +1,143 @@
+//! Backup and restore of the calibration of sensors, with `Export` and
+//! `Import`.
+use std::fs::File;
+use std::io::{Read, Write};
+
+use chrono;
+use toml;
+
+use super::errors::*;
+use super::output::parse_value;
+use super::sensors::{SensorKind, SensorRequester};
+
+/// Reply to `Export` once every calibration string was exported.
+pub const EXPORT_DONE: &str = "*DONE";
+
+/// Most `Export` commands sent, in case the sensor never replies `*DONE`.
+const MAX_EXPORTS: usize = 64;
+
+/// Contents of a backup file.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct Backup {
+    /// Kind of sensor, e.g. `ph`.
+    pub kind: String,
+    /// Reply to `I`, with the type of device and its firmware version.
+    pub device: String,
+    pub firmware: String,
+    pub timestamp: String,
+    /// Calibration strings, in the order they were exported.
+    pub calibration: Vec<String>,
+}
+
+impl Backup {
+    /// Read a backup from a TOML file.
+    pub fn from_file(path: &str) -> Result<Backup> {
+        let mut contents = String::new();
+        let _read = File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .chain_err(|| format!("could not read the backup file: {}", path))?;
+        toml::from_str(&contents).chain_err(|| format!("invalid backup file: {}", path))
+    }
+
+    /// Write the backup to a TOML file.
+    pub fn to_file(&self, path: &str) -> Result<()> {
+        let contents = toml::to_string(self).chain_err(|| "could not write the backup")?;
+        let _write = File::create(path)
+            .and_then(|mut f| f.write_all(contents.as_bytes()))
+            .chain_err(|| format!("could not write the backup file: {}", path))?;
+        Ok(())
+    }
+}
+
+/// Firmware version in the reply to `I`, e.g. `1.98` in `?I,pH,1.98`.
+pub fn firmware(device: &str) -> String {
+    device.rsplit(',').next().unwrap_or("").trim().to_string()
+}
+
+/// Exports the calibration of the sensor.
+pub fn backup(requester: &SensorRequester) -> Result<Backup> {
+    let kind = requester.spec().kind;
+    let device = requester.eval("I")?;
+    // The reply to `Export,?` starts with the number of strings to export.
+    let info = requester.eval("Export,?")?;
+    let expected = parse_value(&info).map(|count| count as usize);
+    info!("exporting the calibration of the {} sensor: {}", kind.name(), info);
+
+    let mut calibration = Vec::new();
+    loop {
+        let exported = requester.eval("Export")?;
+        if exported.contains(EXPORT_DONE) {
+            break;
+        }
+        calibration.push(exported);
+        if calibration.len() >= MAX_EXPORTS {
+            bail!("the sensor exported too many calibration strings");
+        }
+    }
+    if let Some(expected) = expected {
+        if expected != calibration.len() {
+            warn!(
+                "expected {} calibration strings, exported {}",
+                expected,
+                calibration.len()
+            );
+        }
+    }
+
+    Ok(Backup {
+        kind: kind.name().to_string(),
+        firmware: firmware(&device),
+        device,
+        timestamp: chrono::Local::now().to_rfc3339(),
+        calibration,
+    })
+}
+
+/// Imports the calibration into the sensor, which must be of the same kind.
+pub fn restore(requester: &SensorRequester, backup: &Backup) -> Result<()> {
+    let kind = requester.spec().kind;
+    if backup.kind.parse::<SensorKind>()? != kind {
+        bail!(format!(
+            "the backup is of a {} sensor, not of a {} sensor",
+            backup.kind,
+            kind.name()
+        ));
+    }
+    let device = requester.eval("I")?;
+    if firmware(&device) != backup.firmware {
+        warn!(
+            "the backup is of firmware {}, the sensor has firmware {}",
+            backup.firmware,
+            firmware(&device)
+        );
+    }
+    for string in &backup.calibration {
+        let cmd = format!("Import,{}", string);
+        println!("{}: {}", cmd, requester.eval(&cmd)?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn firmware_is_the_last_field_of_the_device_information() {
+        assert_eq!(firmware("?I,pH,1.98"), "1.98");
+        assert_eq!(firmware("1.98"), "1.98");
+    }
+
+    #[test]
+    fn backups_round_trip_through_toml() {
+        let backup = Backup {
+            kind: "ph".to_string(),
+            device: "?I,pH,1.98".to_string(),
+            firmware: "1.98".to_string(),
+            timestamp: "2017-11-20T10:15:00-07:00".to_string(),
+            calibration: vec!["59 6F 75 20 61 72".to_string(), "65 20 61 20 63 6F".to_string()],
+        };
+        let contents = toml::to_string(&backup).unwrap();
+        assert_eq!(toml::from_str::<Backup>(&contents).unwrap(), backup);
+    }
+}
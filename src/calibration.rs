@@ -0,0 +1,245 @@
+//! Guided calibration of pH, conductivity, and temperature sensors.
+use std::io::{self, BufRead, Write};
+use std::thread;
+use std::time::Duration;
+
+use super::errors::*;
+use super::output::parse_value;
+use super::sensors::{SensorKind, SensorRequester};
+
+/// Time between readings, while waiting for them to stabilize.
+const POLL_INTERVAL_MS: u64 = 1000;
+/// Readings that must agree, within the tolerance, to be stable.
+const STABLE_READINGS: usize = 5;
+/// Readings taken before asking whether to calibrate anyway.
+const MAX_READINGS: usize = 120;
+
+/// A calibration point, in the order that the sensor expects them.
+pub struct CalibrationPoint {
+    /// Name of the point, e.g. `mid`.
+    pub name: &'static str,
+    /// Instructions for the operator.
+    pub instructions: &'static str,
+    /// Calibration command, without its value, e.g. `Cal,mid`.
+    pub command: &'static str,
+    /// Usual value of the reference, if the command takes one.
+    pub value: Option<&'static str>,
+    /// Whether readings must stabilize before calibrating.
+    pub stabilize: bool,
+}
+
+static PH_POINTS: [CalibrationPoint; 3] = [
+    CalibrationPoint {
+        name: "mid",
+        instructions: "Rinse the probe, and place it in the mid-point (pH 7.00) solution.",
+        command: "Cal,mid",
+        value: Some("7.00"),
+        stabilize: true,
+    },
+    CalibrationPoint {
+        name: "low",
+        instructions: "Rinse the probe, and place it in the low-point (pH 4.00) solution.",
+        command: "Cal,low",
+        value: Some("4.00"),
+        stabilize: true,
+    },
+    CalibrationPoint {
+        name: "high",
+        instructions: "Rinse the probe, and place it in the high-point (pH 10.00) solution.",
+        command: "Cal,high",
+        value: Some("10.00"),
+        stabilize: true,
+    },
+];
+
+static CONDUCTIVITY_POINTS: [CalibrationPoint; 3] = [
+    CalibrationPoint {
+        name: "dry",
+        instructions: "Dry the probe, and keep it out of any liquid.",
+        command: "Cal,dry",
+        value: None,
+        stabilize: false,
+    },
+    CalibrationPoint {
+        name: "low",
+        instructions: "Place the probe in the low-point (12,880 µS) solution.",
+        command: "Cal,low",
+        value: Some("12880"),
+        stabilize: true,
+    },
+    CalibrationPoint {
+        name: "high",
+        instructions: "Rinse the probe, and place it in the high-point (80,000 µS) solution.",
+        command: "Cal,high",
+        value: Some("80000"),
+        stabilize: true,
+    },
+];
+
+static TEMPERATURE_POINTS: [CalibrationPoint; 1] = [
+    CalibrationPoint {
+        name: "reference",
+        instructions: "Place the probe in water of a known temperature, e.g. boiling water.",
+        command: "Cal",
+        value: Some("100.00"),
+        stabilize: true,
+    },
+];
+
+/// Calibration points of the sensor, or `None` if it has no wizard.
+pub fn points(kind: SensorKind) -> Option<&'static [CalibrationPoint]> {
+    match kind {
+        SensorKind::Ph => Some(&PH_POINTS[..]),
+        SensorKind::Conductivity => Some(&CONDUCTIVITY_POINTS[..]),
+        SensorKind::Temperature => Some(&TEMPERATURE_POINTS[..]),
+    }
+}
+
+/// Largest difference between readings that are stable.
+pub fn tolerance(kind: SensorKind, reading: f64) -> f64 {
+    match kind {
+        SensorKind::Ph => 0.02,
+        SensorKind::Temperature => 0.1,
+        // 1% of the reading, as conductivity spans several orders of magnitude.
+        _ => (reading.abs() * 0.01).max(1.0),
+    }
+}
+
+/// Whether the last `STABLE_READINGS` readings agree within the tolerance.
+pub fn is_stable(kind: SensorKind, readings: &[f64]) -> bool {
+    if readings.len() < STABLE_READINGS {
+        return false;
+    }
+    let last = &readings[readings.len() - STABLE_READINGS..];
+    let min = last.iter().cloned().fold(::std::f64::INFINITY, f64::min);
+    let max = last.iter().cloned().fold(::std::f64::NEG_INFINITY, f64::max);
+    max - min <= tolerance(kind, last[STABLE_READINGS - 1])
+}
+
+/// Walks the operator through every calibration point of the sensor, then
+/// shows the calibration state, and the slope of pH sensors.
+pub fn calibrate(requester: &SensorRequester) -> Result<()> {
+    let kind = requester.spec().kind;
+    let points = match points(kind) {
+        Some(points) => points,
+        None => bail!(format!("there is no calibration wizard for {} sensors", kind.name())),
+    };
+
+    println!("Calibrating the {} sensor, in {} step(s).", kind.name(), points.len());
+    println!("Press ENTER to accept the value, type another one, `s` to skip, or `q` to quit.");
+    for (step, point) in points.iter().enumerate() {
+        println!();
+        println!("Step {}/{}: {}", step + 1, points.len(), point.name);
+        println!("{}", point.instructions);
+        let value = match point.value {
+            Some(value) => match ask(&format!("Value [{}]: ", value))?.as_str() {
+                "q" | "Q" => return Ok(()),
+                "s" | "S" => continue,
+                "" => Some(value.to_string()),
+                other => Some(other.to_string()),
+            },
+            None => match ask("Ready? ")?.as_str() {
+                "q" | "Q" => return Ok(()),
+                "s" | "S" => continue,
+                _ => None,
+            },
+        };
+        if point.stabilize && !wait_for_stable_readings(requester)? {
+            continue;
+        }
+        let cmd = match value {
+            Some(value) => format!("{},{}", point.command, value),
+            None => point.command.to_string(),
+        };
+        println!("{}: {}", cmd, requester.eval(&cmd)?);
+    }
+
+    println!();
+    println!("Calibration state: {}", requester.eval("Cal,?")?);
+    if kind == SensorKind::Ph {
+        println!("Slope: {}", requester.eval("Slope,?")?);
+    }
+    Ok(())
+}
+
+// Polls readings until they are stable. Returns `false` if the operator
+// chooses to skip the point, when they don't stabilize in time.
+fn wait_for_stable_readings(requester: &SensorRequester) -> Result<bool> {
+    let kind = requester.spec().kind;
+    let mut readings = Vec::new();
+    loop {
+        let reply = requester.eval("R")?;
+        match parse_value(&reply) {
+            Some(reading) => {
+                readings.push(reading);
+                println!("Reading: {}", reading);
+            }
+            None => println!("Reading: {} (not a number)", reply),
+        }
+        if is_stable(kind, &readings) {
+            println!("Reading is stable.");
+            return Ok(true);
+        }
+        if readings.len() >= MAX_READINGS {
+            match ask("Reading is not stable, calibrate anyway? [y/N] ")?.as_str() {
+                "y" | "Y" => return Ok(true),
+                _ => return Ok(false),
+            }
+        }
+        thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+    }
+}
+
+// Prints the question, and reads the answer from stdin.
+fn ask(question: &str) -> Result<String> {
+    print!("{}", question);
+    let _flush = io::stdout().flush().chain_err(|| "could not write to stdout")?;
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    read_answer(&mut input)
+}
+
+// Reads one answer. The end of the input answers `q`, so that the wizard
+// quits instead of accepting every default.
+fn read_answer<R: BufRead>(input: &mut R) -> Result<String> {
+    let mut answer = String::new();
+    let read = input
+        .read_line(&mut answer)
+        .chain_err(|| "could not read from stdin")?;
+    if read == 0 {
+        println!();
+        return Ok("q".to_string());
+    }
+    Ok(answer.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readings_are_stable_within_the_tolerance() {
+        let settling = [6.80, 6.95, 7.00, 7.01, 7.00, 7.01];
+        assert!(!is_stable(SensorKind::Ph, &settling));
+        let settled = [6.95, 7.00, 7.01, 7.00, 7.01, 7.00];
+        assert!(is_stable(SensorKind::Ph, &settled));
+        let conductivity = [12800.0, 12850.0, 12880.0, 12870.0, 12900.0];
+        assert!(is_stable(SensorKind::Conductivity, &conductivity));
+        assert!(!is_stable(SensorKind::Temperature, &[25.0, 25.0]));
+    }
+
+    #[test]
+    fn wizards_start_with_the_first_calibration_point() {
+        assert_eq!(points(SensorKind::Ph).unwrap()[0].command, "Cal,mid");
+        assert_eq!(points(SensorKind::Conductivity).unwrap()[0].command, "Cal,dry");
+        assert_eq!(points(SensorKind::Temperature).unwrap()[0].command, "Cal");
+    }
+
+    #[test]
+    fn the_end_of_the_input_quits() {
+        let mut input = io::Cursor::new("\n7.00\n");
+        assert_eq!(read_answer(&mut input).unwrap(), "");
+        assert_eq!(read_answer(&mut input).unwrap(), "7.00");
+        assert_eq!(read_answer(&mut input).unwrap(), "q");
+    }
+}
@@ -1,15 +1,100 @@
 //! Reusable command-line items.
-use clap::{App, AppSettings, Arg, SubCommand};
+use clap::{App, Arg, SubCommand};
 
-/// responder subcommand that reads the `URL`, `I2C_PATH`, and `I2C_ADDRESS`
-/// needed for the service.
+use super::calibration;
+use super::sensors::{SensorKind, SensorSpec};
+
+/// option that reads the name of a sensor in the configuration file, whose
+/// settings are used for the arguments that are not given.
+pub fn name_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("name")
+        .short("n")
+        .long("name")
+        .takes_value(true)
+        .help("Name of the sensor in the configuration file")
+}
+
+/// flag that puts the sensor to sleep when its REP server stops.
+pub fn sleep_on_exit_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("sleep-on-exit")
+        .long("sleep-on-exit")
+        .help("Put the sensor to sleep when the server stops")
+}
+
+/// option that stops the REP server after a number of consecutive requests
+/// failed on the sensor.
+pub fn max_errors_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("max-errors")
+        .long("max-errors")
+        .takes_value(true)
+        .value_name("COUNT")
+        .help("Stop the server after COUNT consecutive requests failed on the sensor")
+}
+
+/// option that sets the pause after every command sent to the sensor.
+pub fn delay_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("delay")
+        .long("delay")
+        .takes_value(true)
+        .value_name("MS")
+        .help("Pause after every command, in milliseconds")
+}
+
+/// option that sets how long REQ clients wait for a reply.
+pub fn timeout_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("timeout")
+        .long("timeout")
+        .takes_value(true)
+        .value_name("MS")
+        .help("How long to wait for a reply, before sending the request again")
+}
+
+/// option that sets how many times REQ clients send a request again.
+pub fn retries_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("retries")
+        .long("retries")
+        .takes_value(true)
+        .value_name("COUNT")
+        .help("How many times to send a request again, when no reply arrives")
+}
+
+/// flag that stops batch mode at the first failed command.
+pub fn fail_fast_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("fail-fast")
+        .long("fail-fast")
+        .help("Stop at the first failed command")
+}
+
+/// option that sets the format of the output of batch mode.
+pub fn output_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("output")
+        .short("o")
+        .long("output")
+        .takes_value(true)
+        .possible_values(&["text", "json", "csv"])
+        .help("Format of the output of batch mode")
+}
+
+/// sensor subcommand that reads the `I2C_PATH` and `I2C_ADDRESS`
+/// needed to access the sensor directly.
 pub fn sensor_subcommand<'a, 'b>() -> App<'a, 'b> {
     SubCommand::with_name("sensor")
         .about("send commands directly over I2C to sensor")
         .args(&[
-            Arg::with_name("I2C_PATH").required(true),
-            Arg::with_name("I2C_ADDRESS").required(true),
+            Arg::with_name("I2C_PATH").required_unless("name"),
+            Arg::with_name("I2C_ADDRESS").required_unless("name"),
         ])
+        .arg(name_arg())
+        .arg(
+            Arg::with_name("cmds")
+                .short("c")
+                .multiple(true)
+                .takes_value(true)
+                .required(false),
+        )
+        .arg(fail_fast_arg())
+        .arg(output_arg())
+        .arg(delay_arg())
 }
 
 /// responder subcommand that reads the `URL`, `I2C_PATH`, and `I2C_ADDRESS`
@@ -19,11 +104,17 @@ pub fn responder_subcommand<'a, 'b>(
     path_env: &'a str,
     addr_env: &'a str,
 ) -> App<'a, 'b> {
-    SubCommand::with_name("rep").about("REP server").args(&[
-        Arg::with_name("URL").env(url_env).required(true),
-        Arg::with_name("I2C_PATH").env(path_env).required(true),
-        Arg::with_name("I2C_ADDRESS").env(addr_env).required(true),
-    ])
+    SubCommand::with_name("rep")
+        .about("REP server")
+        .args(&[
+            Arg::with_name("URL").env(url_env).required_unless("name"),
+            Arg::with_name("I2C_PATH").env(path_env).required_unless("name"),
+            Arg::with_name("I2C_ADDRESS").env(addr_env).required_unless("name"),
+        ])
+        .arg(name_arg())
+        .arg(sleep_on_exit_arg())
+        .arg(max_errors_arg())
+        .arg(delay_arg())
 }
 
 /// requester subcommand that reads the `URL`
@@ -31,7 +122,10 @@ pub fn responder_subcommand<'a, 'b>(
 pub fn requester_subcommand<'a, 'b>(env_var: &'a str) -> App<'a, 'b> {
     SubCommand::with_name("req")
         .about("REQ client")
-        .arg(Arg::with_name("URL").env(env_var).required(true))
+        .arg(Arg::with_name("URL").env(env_var).required_unless("name"))
+        .arg(name_arg())
+        .arg(timeout_arg())
+        .arg(retries_arg())
         .arg(
             Arg::with_name("cmds")
                 .short("c")
@@ -39,6 +133,202 @@ pub fn requester_subcommand<'a, 'b>(env_var: &'a str) -> App<'a, 'b> {
                 .takes_value(true)
                 .required(false),
         )
+        .arg(fail_fast_arg())
+        .arg(output_arg())
+}
+
+/// calibrate subcommand that reads the `URL` of the REP server of the sensor
+/// to calibrate.
+pub fn calibrate_subcommand<'a, 'b>(env_var: &'a str) -> App<'a, 'b> {
+    SubCommand::with_name("calibrate")
+        .about("guided calibration, through the REP server")
+        .arg(Arg::with_name("URL").env(env_var).required_unless("name"))
+        .arg(name_arg())
+        .arg(timeout_arg())
+        .arg(retries_arg())
+}
+
+/// backup subcommand that reads the `URL` of the REP server of the sensor,
+/// and the file to save its calibration to.
+pub fn backup_subcommand<'a, 'b>(env_var: &'a str) -> App<'a, 'b> {
+    SubCommand::with_name("backup")
+        .about("save the calibration to a file, through the REP server")
+        .arg(Arg::with_name("URL").env(env_var).required_unless("name"))
+        .arg(name_arg())
+        .arg(file_arg())
+        .arg(timeout_arg())
+        .arg(retries_arg())
+}
+
+/// restore subcommand that reads the `URL` of the REP server of the sensor,
+/// and the file to load its calibration from.
+pub fn restore_subcommand<'a, 'b>(env_var: &'a str) -> App<'a, 'b> {
+    SubCommand::with_name("restore")
+        .about("load the calibration from a file, through the REP server")
+        .arg(Arg::with_name("URL").env(env_var).required_unless("name"))
+        .arg(name_arg())
+        .arg(file_arg())
+        .arg(timeout_arg())
+        .arg(retries_arg())
+}
+
+/// option that reads the calibration backup file.
+pub fn file_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("file")
+        .short("f")
+        .long("file")
+        .takes_value(true)
+        .required(true)
+        .help("Calibration backup file, in TOML")
+}
+
+/// sensor subcommand, with the `req`, `rep`, and `sensor` subcommands, as
+/// described by the sensor's `SensorSpec`, `backup` and `restore`, and
+/// `calibrate` for the sensors with a calibration wizard.
+pub fn sensor_kind_subcommand<'a, 'b>(spec: &'static SensorSpec) -> App<'a, 'b> {
+    let mut subcommands = vec![
+        requester_subcommand(spec.req_url_env),
+        responder_subcommand(spec.rep_url_env, spec.rep_path_env, spec.rep_address_env),
+        sensor_subcommand(),
+        backup_subcommand(spec.req_url_env),
+        restore_subcommand(spec.req_url_env),
+    ];
+    if calibration::points(spec.kind).is_some() {
+        subcommands.push(calibrate_subcommand(spec.req_url_env));
+    }
+    SubCommand::with_name(spec.name)
+        .about(spec.about)
+        .subcommands(subcommands)
+}
+
+/// serve subcommand that reads the `KIND`, `I2C_PATH`, `I2C_ADDRESS`, and `URL`
+/// of every sensor that is served. Without them, every sensor in the
+/// configuration file with a `bind` URL is served.
+pub fn serve_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("serve")
+        .about("REP servers for several sensors, from one process")
+        .arg(
+            Arg::with_name("sensor")
+                .short("s")
+                .long("sensor")
+                .help("Sensor to serve, may be used more than once")
+                .value_names(&["KIND", "I2C_PATH", "I2C_ADDRESS", "URL"])
+                .number_of_values(4)
+                .multiple(true)
+                .required(false),
+        )
+        .arg(sleep_on_exit_arg())
+        .arg(max_errors_arg())
+        .arg(delay_arg())
+}
+
+/// watch subcommand that sends a command to several sensors periodically.
+pub fn watch_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("watch")
+        .about("send a command to several sensors periodically")
+        .arg(
+            Arg::with_name("sensor")
+                .short("s")
+                .long("sensor")
+                .help("Sensor to watch, may be used more than once")
+                .value_names(&["KIND", "URL"])
+                .number_of_values(2)
+                .multiple(true),
+        )
+        .arg(
+            name_arg()
+                .multiple(true)
+                .number_of_values(1)
+                .help("Name of a sensor in the configuration file, may be used more than once"),
+        )
+        .arg(
+            Arg::with_name("command")
+                .long("command")
+                .takes_value(true)
+                .default_value("R")
+                .help("Command to send"),
+        )
+        .arg(
+            Arg::with_name("interval")
+                .short("i")
+                .long("interval")
+                .takes_value(true)
+                .default_value("5s")
+                .help("Time between rounds, e.g. 500ms, 5s, or 1m"),
+        )
+        .arg(
+            Arg::with_name("count")
+                .long("count")
+                .takes_value(true)
+                .help("Number of rounds, or until interrupted"),
+        )
+        .arg(
+            Arg::with_name("compensate")
+                .long("compensate")
+                .takes_value(true)
+                .value_name("URL")
+                .help("REP server of a temperature sensor, to compensate the readings for"),
+        )
+        .arg(
+            Arg::with_name("compensate-name")
+                .long("compensate-name")
+                .takes_value(true)
+                .value_name("NAME")
+                .conflicts_with("compensate")
+                .help("Name of a temperature sensor in the configuration file, to compensate for"),
+        )
+        .arg(timeout_arg())
+        .arg(retries_arg())
+        .arg(output_arg().help("Format of the output"))
+}
+
+/// record subcommand that stores readings of the sensors in the configuration
+/// file, to CSV files or a SQLite database.
+pub fn record_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("record")
+        .about("store readings of the sensors in the configuration file")
+        .arg(
+            name_arg()
+                .multiple(true)
+                .number_of_values(1)
+                .help("Name of a sensor to record, every sensor if not given"),
+        )
+        .arg(
+            Arg::with_name("interval")
+                .short("i")
+                .long("interval")
+                .takes_value(true)
+                .default_value("1m")
+                .help("Time between readings, e.g. 30s, or 5m"),
+        )
+        .arg(
+            Arg::with_name("count")
+                .long("count")
+                .takes_value(true)
+                .help("Number of readings, or until interrupted"),
+        )
+        .arg(
+            Arg::with_name("direct")
+                .long("direct")
+                .help("Read the sensors over I2C, instead of their REP servers"),
+        )
+        .arg(
+            Arg::with_name("csv-dir")
+                .long("csv-dir")
+                .takes_value(true)
+                .value_name("DIR")
+                .help("Directory for the CSV files, one per day"),
+        )
+        .arg(
+            Arg::with_name("sqlite")
+                .long("sqlite")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with("csv-dir")
+                .help("SQLite database, instead of CSV files"),
+        )
+        .arg(timeout_arg())
+        .arg(retries_arg())
 }
 
 /// Parser for the main program
@@ -47,35 +337,86 @@ pub struct BenitaCommanderApp;
 impl BenitaCommanderApp {
     pub fn new<'a, 'b>() -> App<'a, 'b> {
         App::new("benita-commander")
-            .settings(&[AppSettings::ArgsNegateSubcommands])
-            .subcommands(vec![
-                SubCommand::with_name("conductivity")
-                    .about("Commands for conductivity")
-                    .subcommands(vec![
-                        requester_subcommand("CONDUCTIVITY_REQ_URL"),
-                        responder_subcommand(
-                            "CONDUCTIVITY_REP_URL",
-                            "CONDUCTIVITY_REP_PATH",
-                            "CONDUCTIVITY_REP_ADDRESS",
-                        ),
-                    ]),
-                SubCommand::with_name("ph")
-                    .about("Commands for pH")
-                    .subcommands(vec![
-                        requester_subcommand("PH_REQ_URL"),
-                        responder_subcommand("PH_REP_URL", "PH_REP_PATH", "PH_REP_ADDRESS"),
-                    ]),
-                SubCommand::with_name("temperature")
-                    .about("Commands for temperature")
-                    .subcommands(vec![
-                        requester_subcommand("TEMPERATURE_REQ_URL"),
-                        responder_subcommand(
-                            "TEMPERATURE_REP_URL",
-                            "TEMPERATURE_REP_PATH",
-                            "TEMPERATURE_REP_ADDRESS",
-                        ),
-                    ]),
-            ])
+            .arg(
+                Arg::with_name("config")
+                    .long("config")
+                    .env("BENITA_CONFIG")
+                    .takes_value(true)
+                    .global(true)
+                    .help("Configuration file, in TOML"),
+            )
+            .arg(
+                Arg::with_name("verbose")
+                    .short("v")
+                    .long("verbose")
+                    .multiple(true)
+                    .global(true)
+                    .help("Log more, once per level, e.g. `-vv` for trace"),
+            )
+            .arg(
+                Arg::with_name("quiet")
+                    .short("q")
+                    .long("quiet")
+                    .multiple(true)
+                    .global(true)
+                    .conflicts_with("verbose")
+                    .help("Log less, once per level, e.g. `-qq` for errors only"),
+            )
+            .arg(
+                Arg::with_name("log-level")
+                    .long("log-level")
+                    .takes_value(true)
+                    .value_name("LEVEL")
+                    .possible_values(&["off", "error", "warn", "info", "debug", "trace"])
+                    .global(true)
+                    .help("Log level, instead of the one in the configuration file"),
+            )
+            .arg(
+                Arg::with_name("log-dir")
+                    .long("log-dir")
+                    .takes_value(true)
+                    .value_name("DIR")
+                    .global(true)
+                    .help("Directory for the log files"),
+            )
+            .arg(
+                Arg::with_name("log-format")
+                    .long("log-format")
+                    .takes_value(true)
+                    .value_name("FORMAT")
+                    .possible_values(&["text", "json"])
+                    .global(true)
+                    .help("Format of the log messages"),
+            )
+            .arg(
+                Arg::with_name("syslog")
+                    .long("syslog")
+                    .global(true)
+                    .help("Log to syslog, or journald, instead of the terminal"),
+            )
+            .arg(
+                Arg::with_name("history-file")
+                    .long("history-file")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .global(true)
+                    .help("History file of the interactive shell"),
+            )
+            .arg(
+                Arg::with_name("no-log-file")
+                    .long("no-log-file")
+                    .global(true)
+                    .help("Only log to the terminal"),
+            )
+            .subcommands(
+                SensorKind::all()
+                    .iter()
+                    .map(|kind| sensor_kind_subcommand(kind.spec()))
+                    .collect::<Vec<App>>(),
+            )
+            .subcommand(serve_subcommand())
+            .subcommand(watch_subcommand())
+            .subcommand(record_subcommand())
     }
 }
 
@@ -218,4 +559,337 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn matching_conductivity_valid_sensor_subcommands() {
+        let mut app = BenitaCommanderApp::new();
+        test_valid!(app, &["benita-commander", "conductivity", "sensor", "path", "i2c"]);
+        test_valid!(
+            app,
+            &[
+                "benita-commander",
+                "conductivity",
+                "sensor",
+                "path",
+                "i2c",
+                "-c",
+                "R",
+                "Status"
+            ]
+        );
+    }
+
+    #[test]
+    fn matching_conductivity_invalid_sensor_subcommands_yields_err() {
+        let mut app = BenitaCommanderApp::new();
+        test_invalid!(app, &["benita-commander", "conductivity", "sensor"]);
+        test_invalid!(app, &["benita-commander", "conductivity", "sensor", "path"]);
+        test_invalid!(
+            app,
+            &["benita-commander", "conductivity", "sensor", "path", "i2c", "extra"]
+        );
+    }
+
+    #[test]
+    fn matching_ph_valid_sensor_subcommands() {
+        let mut app = BenitaCommanderApp::new();
+        test_valid!(app, &["benita-commander", "ph", "sensor", "path", "i2c"]);
+        test_valid!(
+            app,
+            &[
+                "benita-commander",
+                "ph",
+                "sensor",
+                "path",
+                "i2c",
+                "-c",
+                "R",
+                "Status"
+            ]
+        );
+    }
+
+    #[test]
+    fn matching_ph_invalid_sensor_subcommands_yields_err() {
+        let mut app = BenitaCommanderApp::new();
+        test_invalid!(app, &["benita-commander", "ph", "sensor"]);
+        test_invalid!(app, &["benita-commander", "ph", "sensor", "path"]);
+        test_invalid!(
+            app,
+            &["benita-commander", "ph", "sensor", "path", "i2c", "extra"]
+        );
+    }
+
+    #[test]
+    fn matching_temperature_valid_sensor_subcommands() {
+        let mut app = BenitaCommanderApp::new();
+        test_valid!(app, &["benita-commander", "temperature", "sensor", "path", "i2c"]);
+        test_valid!(
+            app,
+            &[
+                "benita-commander",
+                "temperature",
+                "sensor",
+                "path",
+                "i2c",
+                "-c",
+                "R",
+                "Status"
+            ]
+        );
+    }
+
+    #[test]
+    fn matching_temperature_invalid_sensor_subcommands_yields_err() {
+        let mut app = BenitaCommanderApp::new();
+        test_invalid!(app, &["benita-commander", "temperature", "sensor"]);
+        test_invalid!(app, &["benita-commander", "temperature", "sensor", "path"]);
+        test_invalid!(
+            app,
+            &["benita-commander", "temperature", "sensor", "path", "i2c", "extra"]
+        );
+    }
+
+    #[test]
+    fn matching_serve_valid_subcommands() {
+        let mut app = BenitaCommanderApp::new();
+        test_valid!(
+            app,
+            &["benita-commander", "serve", "-s", "ph", "path", "i2c", "url"]
+        );
+        test_valid!(
+            app,
+            &[
+                "benita-commander",
+                "serve",
+                "-s",
+                "ph",
+                "path",
+                "i2c",
+                "url",
+                "-s",
+                "temperature",
+                "path",
+                "i2c",
+                "url"
+            ]
+        );
+        test_valid!(
+            app,
+            &[
+                "benita-commander",
+                "serve",
+                "--sleep-on-exit",
+                "-s",
+                "ph",
+                "path",
+                "i2c",
+                "url"
+            ]
+        );
+        test_valid!(
+            app,
+            &["benita-commander", "ph", "rep", "url", "path", "i2c", "--sleep-on-exit"]
+        );
+    }
+
+    #[test]
+    fn matching_serve_invalid_subcommands_yields_err() {
+        let mut app = BenitaCommanderApp::new();
+        test_invalid!(app, &["benita-commander", "serve", "-s", "ph", "path", "i2c"]);
+    }
+
+    #[test]
+    fn matching_named_sensor_valid_subcommands() {
+        let mut app = BenitaCommanderApp::new();
+        test_valid!(app, &["benita-commander", "ph", "rep", "--name", "tank-ph"]);
+        test_valid!(app, &["benita-commander", "ph", "req", "-n", "tank-ph"]);
+        test_valid!(app, &["benita-commander", "ph", "sensor", "-n", "tank-ph"]);
+        test_valid!(
+            app,
+            &[
+                "benita-commander",
+                "ph",
+                "req",
+                "--config",
+                "benita.toml",
+                "-n",
+                "tank-ph",
+                "-c",
+                "R"
+            ]
+        );
+        test_valid!(app, &["benita-commander", "serve", "--config", "benita.toml"]);
+        test_valid!(
+            app,
+            &["benita-commander", "--config", "benita.toml", "ph", "rep", "-n", "tank-ph"]
+        );
+        test_valid!(app, &["benita-commander", "--config", "benita.toml", "serve"]);
+        test_valid!(app, &["benita-commander", "--config", "benita.toml", "watch"]);
+        test_valid!(app, &["benita-commander", "--config", "benita.toml", "record"]);
+    }
+
+    #[test]
+    fn matching_global_logging_options() {
+        let mut app = BenitaCommanderApp::new();
+        test_valid!(app, &["benita-commander", "-vv", "ph", "req", "-n", "tank-ph"]);
+        test_valid!(app, &["benita-commander", "serve", "-q", "--no-log-file"]);
+        test_valid!(
+            app,
+            &["benita-commander", "--log-level", "debug", "--log-dir", "/tmp", "serve"]
+        );
+        test_valid!(app, &["benita-commander", "serve", "--log-format", "json", "--syslog"]);
+        test_valid!(app, &["benita-commander", "-q", "--no-log-file", "watch"]);
+        test_invalid!(app, &["benita-commander", "serve", "--log-level", "loud"]);
+        test_valid!(app, &["benita-commander", "--log-format", "json", "--syslog", "serve"]);
+        test_invalid!(app, &["benita-commander", "serve", "--log-format", "xml"]);
+        test_invalid!(app, &["benita-commander", "serve", "-v", "-q"]);
+        test_invalid!(app, &["benita-commander", "-v", "-q", "serve"]);
+        test_valid!(app, &["benita-commander", "--history-file", "ph.history"]);
+        test_valid!(
+            app,
+            &["benita-commander", "ph", "sensor", "--history-file", "ph.history", "-n", "tank-ph"]
+        );
+        test_valid!(
+            app,
+            &["benita-commander", "--history-file", "ph.history", "ph", "req", "-n", "tank-ph"]
+        );
+    }
+
+    #[test]
+    fn matching_requester_timeout_and_retries() {
+        let mut app = BenitaCommanderApp::new();
+        test_valid!(
+            app,
+            &[
+                "benita-commander",
+                "ph",
+                "req",
+                "url",
+                "--timeout",
+                "1000",
+                "--retries",
+                "3",
+                "-c",
+                "R"
+            ]
+        );
+        test_invalid!(app, &["benita-commander", "ph", "req", "url", "--timeout"]);
+    }
+
+    #[test]
+    fn matching_batch_fail_fast() {
+        let mut app = BenitaCommanderApp::new();
+        test_valid!(
+            app,
+            &["benita-commander", "ph", "req", "url", "--fail-fast", "-c", "R", "L,1"]
+        );
+        test_valid!(
+            app,
+            &["benita-commander", "ph", "sensor", "path", "99", "--fail-fast", "-c", "R"]
+        );
+        test_valid!(
+            app,
+            &["benita-commander", "ph", "sensor", "path", "99", "--delay", "1000", "-c", "R"]
+        );
+    }
+
+    #[test]
+    fn matching_watch_subcommands() {
+        let mut app = BenitaCommanderApp::new();
+        test_valid!(app, &["benita-commander", "watch", "-s", "ph", "url"]);
+        test_valid!(
+            app,
+            &[
+                "benita-commander",
+                "watch",
+                "-s",
+                "ph",
+                "url",
+                "-n",
+                "tank-ec",
+                "-n",
+                "tank-do",
+                "--interval",
+                "10s",
+                "--count",
+                "3",
+                "--command",
+                "R"
+            ]
+        );
+        test_valid!(
+            app,
+            &[
+                "benita-commander",
+                "watch",
+                "-s",
+                "ph",
+                "url",
+                "--compensate",
+                "temperature-url"
+            ]
+        );
+        test_invalid!(app, &["benita-commander", "watch", "-s", "ph"]);
+    }
+
+    #[test]
+    fn matching_record_subcommands() {
+        let mut app = BenitaCommanderApp::new();
+        test_valid!(app, &["benita-commander", "record"]);
+        test_valid!(
+            app,
+            &[
+                "benita-commander",
+                "record",
+                "-n",
+                "tank-ph",
+                "--interval",
+                "30s",
+                "--csv-dir",
+                "readings"
+            ]
+        );
+        test_valid!(app, &["benita-commander", "record", "--direct", "--sqlite", "readings.db"]);
+        test_invalid!(
+            app,
+            &["benita-commander", "record", "--csv-dir", "readings", "--sqlite", "readings.db"]
+        );
+    }
+
+    #[test]
+    fn matching_calibrate_subcommands() {
+        let mut app = BenitaCommanderApp::new();
+        test_valid!(app, &["benita-commander", "ph", "calibrate", "url"]);
+        test_valid!(app, &["benita-commander", "conductivity", "calibrate", "-n", "tank-ec"]);
+        test_valid!(app, &["benita-commander", "temperature", "calibrate", "url"]);
+    }
+
+    #[test]
+    fn matching_backup_and_restore_subcommands() {
+        let mut app = BenitaCommanderApp::new();
+        test_valid!(app, &["benita-commander", "ph", "backup", "url", "-f", "ph.toml"]);
+        test_valid!(
+            app,
+            &["benita-commander", "ph", "restore", "-n", "tank-ph", "--file", "ph.toml"]
+        );
+        test_invalid!(app, &["benita-commander", "ph", "backup", "url"]);
+    }
+
+    #[test]
+    fn matching_batch_output_formats() {
+        let mut app = BenitaCommanderApp::new();
+        test_valid!(
+            app,
+            &["benita-commander", "ph", "req", "url", "--output", "json", "-c", "R"]
+        );
+        test_valid!(
+            app,
+            &["benita-commander", "ph", "sensor", "path", "99", "-o", "csv", "-c", "R"]
+        );
+        test_invalid!(
+            app,
+            &["benita-commander", "ph", "req", "url", "--output", "xml", "-c", "R"]
+        );
+    }
 }
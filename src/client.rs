@@ -0,0 +1,191 @@
+//! REQ clients of the REP servers, and their options.
+use std::cell::RefCell;
+
+use zmq;
+
+use super::errors::*;
+use super::server::ERROR_REPLY_PREFIX;
+
+/// How long a REQ client waits for a reply, in milliseconds.
+pub const DEFAULT_TIMEOUT_MS: i32 = 3000;
+
+/// How many times a REQ client sends a request again, when no reply arrives.
+pub const DEFAULT_RETRIES: u32 = 2;
+
+/// Timeout and retries of a REQ client.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientConfig {
+    pub timeout_ms: i32,
+    pub retries: u32,
+}
+
+impl Default for ClientConfig {
+    fn default() -> ClientConfig {
+        ClientConfig {
+            timeout_ms: DEFAULT_TIMEOUT_MS,
+            retries: DEFAULT_RETRIES,
+        }
+    }
+}
+
+/// A REQ client, following the "lazy pirate" pattern: when no reply arrives
+/// in time, the socket is closed, and the request is sent again from a new
+/// one.
+pub struct Client {
+    socket: RefCell<zmq::Socket>,
+    url: String,
+    config: ClientConfig,
+}
+
+impl Client {
+    pub fn connect(url: &str, config: &ClientConfig) -> Result<Client> {
+        let socket = connect_requester(url, config)?;
+        Ok(Client {
+            socket: RefCell::new(socket),
+            url: url.to_string(),
+            config: config.clone(),
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Sends the request, and returns its reply. Only requests that time out
+    /// are sent again, since other failures would fail again. Error replies
+    /// of the server are `DeviceFailed` errors.
+    pub fn request(&self, request: &str) -> Result<String> {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let exchanged = self.exchange(request);
+            if exchanged.is_err() {
+                // The socket may still wait for the lost reply, and would
+                // refuse to send anything else.
+                *self.socket.borrow_mut() = connect_requester(&self.url, &self.config)?;
+            }
+            match exchanged {
+                Ok(reply) => return device_reply(reply),
+                Err(zmq::Error::EAGAIN) if attempts <= self.config.retries => {
+                    warn!(
+                        "no reply from {}, retrying ({}/{})",
+                        self.url, attempts, self.config.retries
+                    );
+                }
+                Err(e) => {
+                    return Err(e)
+                        .chain_err(|| format!("no reply after {} attempt(s)", attempts))
+                        .chain_err(|| ErrorKind::TransportFailed(self.url.clone()));
+                }
+            }
+        }
+    }
+
+    fn exchange(&self, request: &str) -> ::std::result::Result<String, zmq::Error> {
+        let socket = self.socket.borrow();
+        socket.send(request.as_bytes(), 0)?;
+        let reply = socket.recv_bytes(0)?;
+        Ok(String::from_utf8_lossy(&reply).into_owned())
+    }
+}
+
+// Connects a REQ socket that gives up on sending and receiving after the
+// timeout.
+fn connect_requester(url: &str, config: &ClientConfig) -> Result<zmq::Socket> {
+    let context = zmq::Context::new();
+    let socket = context
+        .socket(zmq::REQ)
+        .chain_err(|| "could not create the REQ socket")?;
+    let _timeout = socket
+        .set_rcvtimeo(config.timeout_ms)
+        .and_then(|_| socket.set_sndtimeo(config.timeout_ms))
+        .chain_err(|| "unable to set the socket timeouts")?;
+    // Sockets are dropped when a reply is lost, with their pending requests.
+    let _linger = socket
+        .set_linger(0)
+        .chain_err(|| "unable to set the socket linger period")?;
+    let _connect = socket
+        .connect(url)
+        .chain_err(|| format!("could not connect to {}", url))?;
+    Ok(socket)
+}
+
+// Replies that start with `ERROR_REPLY_PREFIX` report a failed request.
+fn device_reply(reply: String) -> Result<String> {
+    if reply.starts_with(ERROR_REPLY_PREFIX) {
+        let reason = reply[ERROR_REPLY_PREFIX.len()..].to_string();
+        bail!(ErrorKind::DeviceFailed(reason));
+    }
+    Ok(reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    // A ROUTER socket stands in for a REP server, to see every attempt
+    // without having to reply to it.
+    fn bind_server() -> (zmq::Socket, String) {
+        let server = zmq::Context::new().socket(zmq::ROUTER).unwrap();
+        server.set_rcvtimeo(1000).unwrap();
+        server.bind("tcp://127.0.0.1:*").unwrap();
+        let url = server.get_last_endpoint().unwrap().unwrap();
+        (server, url)
+    }
+
+    fn config(retries: u32) -> ClientConfig {
+        ClientConfig {
+            timeout_ms: 100,
+            retries,
+        }
+    }
+
+    #[test]
+    fn requests_without_reply_are_sent_again() {
+        let (server, url) = bind_server();
+        let client = Client::connect(&url, &config(2)).unwrap();
+        let e = client.request("R").unwrap_err();
+        match *e.kind() {
+            ErrorKind::TransportFailed(ref failed) => assert_eq!(failed, &url),
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+        for _ in 0..3 {
+            let parts = server.recv_multipart(0).unwrap();
+            assert_eq!(parts.last().unwrap(), b"R");
+        }
+        assert!(server.recv_multipart(0).is_err());
+    }
+
+    #[test]
+    fn sockets_are_replaced_after_the_last_attempt() {
+        let (server, url) = bind_server();
+        let replier = thread::spawn(move || {
+            let _lost = server.recv_multipart(0).unwrap();
+            let parts = server.recv_multipart(0).unwrap();
+            let reply: Vec<&[u8]> = vec![&parts[0][..], &parts[1][..], &b"7.02"[..]];
+            server.send_multipart(&reply, 0).unwrap();
+        });
+        let client = Client::connect(&url, &config(0)).unwrap();
+        assert!(client.request("R").is_err());
+        assert_eq!(client.request("R").unwrap(), "7.02");
+        replier.join().unwrap();
+    }
+
+    #[test]
+    fn error_replies_are_device_failures() {
+        let (server, url) = bind_server();
+        let replier = thread::spawn(move || {
+            let parts = server.recv_multipart(0).unwrap();
+            let reply: Vec<&[u8]> = vec![&parts[0][..], &parts[1][..], &b"error: I2C failed"[..]];
+            server.send_multipart(&reply, 0).unwrap();
+        });
+        let client = Client::connect(&url, &config(0)).unwrap();
+        let e = client.request("R").unwrap_err();
+        match *e.kind() {
+            ErrorKind::DeviceFailed(ref reason) => assert_eq!(reason, "I2C failed"),
+            ref kind => panic!("unexpected error: {:?}", kind),
+        }
+        replier.join().unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+//! Tab completion of the commands of the interactive shells, from their
+//! syntax, e.g. `Cal,mid,<pH>`.
+use rustyline;
+use rustyline::completion::Completer;
+
+use super::sensors::SensorKind;
+
+/// Completes the commands of a shell. Keywords complete up to their first
+/// argument, e.g. `Cal,m` to `Cal,mid,`, and `<sensor>` arguments complete
+/// to the kinds of sensors.
+pub struct CommandCompleter {
+    commands: &'static [&'static str],
+}
+
+impl CommandCompleter {
+    pub fn new(commands: &'static [&'static str]) -> CommandCompleter {
+        CommandCompleter { commands }
+    }
+
+    /// Start of the word being completed, and its candidates.
+    pub fn candidates(&self, line: &str) -> (usize, Vec<String>) {
+        let start = match line.rfind(' ') {
+            Some(space) => space + 1,
+            None => {
+                let mut keywords: Vec<String> = self.commands
+                    .iter()
+                    .map(|cmd| keyword(cmd))
+                    .filter(|candidate| starts_with_ignore_case(candidate, line))
+                    .map(|candidate| candidate.to_string())
+                    .collect();
+                keywords.dedup();
+                return (0, keywords);
+            }
+        };
+        let word = &line[start..];
+        let position = line[..start].split_whitespace().count();
+        let first = line.split_whitespace().next().unwrap_or("").to_lowercase();
+        let takes_sensor = self.commands.iter().any(|cmd| {
+            let tokens: Vec<&str> = cmd.split_whitespace().collect();
+            tokens.len() > position && tokens[0].to_lowercase() == first
+                && tokens[position] == "<sensor>"
+        });
+        if !takes_sensor {
+            return (start, Vec::new());
+        }
+        let sensors = SensorKind::all()
+            .iter()
+            .map(|kind| kind.name())
+            .filter(|name| starts_with_ignore_case(name, word))
+            .map(|name| name.to_string())
+            .collect();
+        (start, sensors)
+    }
+
+    /// Syntax of the commands that the line starts, or is an instance of,
+    /// e.g. `Cal,mid,<pH>` for `cal,m` or `Cal,mid,7.00`.
+    pub fn hints(&self, line: &str) -> Vec<&'static str> {
+        self.commands
+            .iter()
+            .filter(|cmd| {
+                let literal = keyword(cmd);
+                starts_with_ignore_case(literal, line)
+                    || (literal.len() < cmd.len() && starts_with_ignore_case(line, literal))
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+impl Completer for CommandCompleter {
+    fn complete(&self, line: &str, pos: usize) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(&line[..pos]))
+    }
+}
+
+/// Literal start of the syntax of a command, up to its first argument, e.g.
+/// `Cal,mid,` for `Cal,mid,<pH>`, or `connect ` for `connect <sensor> <url>`.
+pub fn keyword(syntax: &str) -> &str {
+    syntax.split('<').next().unwrap_or(syntax)
+}
+
+fn starts_with_ignore_case(s: &str, prefix: &str) -> bool {
+    s.to_lowercase().starts_with(&prefix.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use console;
+    use ph;
+
+    #[test]
+    fn keywords_complete_up_to_their_arguments() {
+        let completer = CommandCompleter::new(ph::COMMANDS);
+        assert_eq!(completer.candidates("cal,m"), (0, vec!["Cal,mid,".to_string()]));
+        assert_eq!(
+            completer.candidates("T"),
+            (0, vec!["T,?".to_string(), "T,".to_string()])
+        );
+        assert_eq!(completer.candidates("Zzz"), (0, Vec::new()));
+    }
+
+    #[test]
+    fn sensor_arguments_complete_to_the_kinds_of_sensors() {
+        let completer = CommandCompleter::new(console::COMMANDS);
+        assert_eq!(completer.candidates("con"), (0, vec!["connect ".to_string()]));
+        assert_eq!(
+            completer.candidates("use con"),
+            (4, vec!["conductivity".to_string()])
+        );
+        assert_eq!(completer.candidates("connect ph tcp"), (11, Vec::new()));
+    }
+
+    #[test]
+    fn hints_show_the_syntax_of_the_arguments() {
+        let completer = CommandCompleter::new(ph::COMMANDS);
+        assert_eq!(completer.hints("Cal,mid"), vec!["Cal,mid,<pH>"]);
+        assert_eq!(completer.hints("cal,mid,7.00"), vec!["Cal,mid,<pH>"]);
+        assert_eq!(completer.hints("T,"), vec!["T,?", "T,<°C>"]);
+    }
+}
@@ -6,99 +6,61 @@ mod errors {
 pub use self::errors::*;
 
 use benita::ezo::conductivity::device::ConductivitySensor;
-use benita::ezo::conductivity::network::{ConductivityRequester, ConductivityResponder};
 use benita::ezo::conductivity::network::requests::*;
 use benita::ezo::devices::{I2CCommand, I2CResponse};
-use benita::ezo::network::{Endpoint, SocketReply, SocketRequest};
-use benita::utilities::{create_and_bind_responder, create_and_connect_requester};
+use benita::ezo::network::SocketRequest;
+use sensors::{SensorKind, SensorSpec};
 
-// Creates a requester with an explicit list of commands that it handles.
-requester! {
-    ConductivityREQ,
-     "A request (REQ) client for Conductivity servers which handles a list of commands.",
-     ConductivityRequester,
-     ConductivityRequester,
-     [
-         CalibrationClear,
-         CalibrationDry,
-         CalibrationHigh,
-         CalibrationLow,
-         CalibrationOnePoint,
-         CalibrationState,
-         CompensationGet,
-         CompensationSet,
-         DeviceInformation,
-         Export,
-         ExportInfo,
-         Import,
-         Find,
-         LedOff,
-         LedOn,
-         LedState,
-         OutputDisableConductivity,
-         OutputEnableConductivity,
-         OutputDisableSalinity,
-         OutputEnableSalinity,
-         OutputDisableSpecificGravity,
-         OutputEnableSpecificGravity,
-         OutputDisableTds,
-         OutputEnableTds,
-         OutputState,
-         ProbeTypeOne,
-         ProbeTypePointOne,
-         ProbeTypeState,
-         ProbeTypeTen,
-         ProtocolLockDisable,
-         ProtocolLockEnable,
-         ProtocolLockState,
-         Reading,
-         Status,
-         Sleep
-     ]
-}
-
-// Creates a responder with an explicit list of commands that it handles.
-responder! {
-    ConductivityREP,
-     "A response (REP) server for a Conductivity device which handles a list of commands.",
-     ConductivitySensor,
-     ConductivityResponder,
-     ConductivityResponder,
-     [
-         CalibrationClear,
-         CalibrationDry,
-         CalibrationHigh,
-         CalibrationLow,
-         CalibrationOnePoint,
-         CalibrationState,
-         CompensationGet,
-         CompensationSet,
-         DeviceInformation,
-         Export,
-         ExportInfo,
-         Import,
-         Find,
-         LedOff,
-         LedOn,
-         LedState,
-         OutputDisableConductivity,
-         OutputEnableConductivity,
-         OutputDisableSalinity,
-         OutputEnableSalinity,
-         OutputDisableSpecificGravity,
-         OutputEnableSpecificGravity,
-         OutputDisableTds,
-         OutputEnableTds,
-         OutputState,
-         ProbeTypeOne,
-         ProbeTypePointOne,
-         ProbeTypeState,
-         ProbeTypeTen,
-         ProtocolLockDisable,
-         ProtocolLockEnable,
-         ProtocolLockState,
-         Reading,
-         Status,
-         Sleep
-     ]
+// Describes Conductivity sensors, and creates their REQ client, REP server, and
+// I2C device handler, which handle the requests below.
+sensor! {
+    ConductivitySensor,
+    SensorKind::Conductivity,
+    "conductivity",
+    "Commands for conductivity",
+    "CONDUCTIVITY",
+    "µS/cm",
+    ConductivityREQ:
+        "A request (REQ) client for Conductivity servers which handles a list of commands.",
+    ConductivityREP:
+        "A response (REP) server for a Conductivity device which handles a list of commands.",
+    ConductivityDevice:
+        "A direct I2C handler for a Conductivity device which handles a list of commands.",
+    [
+        CalibrationClear => "Cal,clear",
+        CalibrationDry => "Cal,dry",
+        CalibrationHigh => "Cal,high,<µS>",
+        CalibrationLow => "Cal,low,<µS>",
+        CalibrationOnePoint => "Cal,<µS>",
+        CalibrationState => "Cal,?",
+        CompensationGet => "T,?",
+        CompensationSet => "T,<°C>",
+        DeviceInformation => "I",
+        Export => "Export",
+        ExportInfo => "Export,?",
+        Import => "Import,<calibration>",
+        Find => "Find",
+        LedOff => "L,0",
+        LedOn => "L,1",
+        LedState => "L,?",
+        OutputDisableConductivity => "O,EC,0",
+        OutputEnableConductivity => "O,EC,1",
+        OutputDisableSalinity => "O,S,0",
+        OutputEnableSalinity => "O,S,1",
+        OutputDisableSpecificGravity => "O,SG,0",
+        OutputEnableSpecificGravity => "O,SG,1",
+        OutputDisableTds => "O,TDS,0",
+        OutputEnableTds => "O,TDS,1",
+        OutputState => "O,?",
+        ProbeTypeOne => "K,1.0",
+        ProbeTypePointOne => "K,0.1",
+        ProbeTypeState => "K,?",
+        ProbeTypeTen => "K,10.0",
+        ProtocolLockDisable => "Plock,0",
+        ProtocolLockEnable => "Plock,1",
+        ProtocolLockState => "Plock,?",
+        Reading => "R",
+        Status => "Status",
+        Sleep => "Sleep"
+    ]
 }
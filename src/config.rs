@@ -0,0 +1,343 @@
+//! Configuration file for sensors, endpoints, and logging.
+//!
+//! ```toml
+//! [logging]
+//! level = "info"
+//! dir = "/var/log/benita-commander"
+//! files = true
+//! format = "text"
+//! syslog = false
+//!
+//! [logging.targets]
+//! benita = "debug"
+//!
+//! [logging.rotation."benita-commander.log"]
+//! max_size_kb = 10240
+//! daily = true
+//! keep = 7
+//! compress = true
+//!
+//! [history]
+//! dir = "/home/pi/.local/share/benita-commander/history"
+//! max_size = 1000
+//!
+//! [sensors.tank-ph]
+//! kind = "ph"
+//! path = "/dev/i2c-1"
+//! address = 99
+//! bind = "tcp://*:7778"
+//! connect = "tcp://192.168.1.10:7778"
+//! sleep_on_exit = true
+//! max_errors = 5
+//! timeout_ms = 3000
+//! retries = 2
+//! ```
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+
+use toml;
+
+use super::sensors::SensorKind;
+use super::server::ServerConfig;
+
+mod errors {
+    //! Errors and their descriptions
+    use std::io;
+    use toml;
+
+    error_chain! {
+        errors {
+            ConfigFile(path: String) {
+                description ("could not read the configuration file")
+                display ("could not read the configuration file: {}", path)
+            }
+            UnknownSensorName(name: String) {
+                description ("sensor not found in the configuration file")
+                display ("sensor not found in the configuration file: {}", name)
+            }
+            WrongSensorKind(name: String, kind: String) {
+                description ("sensor in the configuration file is of another kind")
+                display ("sensor in the configuration file is not a {} sensor: {}", kind, name)
+            }
+            InvalidAddress(address: String) {
+                description ("invalid I2C address")
+                display ("invalid I2C address: {}", address)
+            }
+            UnknownSensorKind(kind: String) {
+                description ("unknown sensor kind")
+                display ("unknown sensor kind: {}", kind)
+            }
+            MissingSetting(setting: String) {
+                description ("missing setting")
+                display ("missing setting: {}", setting)
+            }
+        }
+        foreign_links {
+            Io(io::Error);
+            Toml(toml::de::Error);
+        }
+    }
+}
+
+pub use self::errors::*;
+
+/// Contents of the configuration file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub logging: Option<LoggingConfig>,
+    pub history: Option<HistoryConfig>,
+    #[serde(default)]
+    pub sensors: BTreeMap<String, SensorConfig>,
+}
+
+/// Logging settings.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// Level for every target, e.g. `info`.
+    pub level: Option<String>,
+    /// Directory for the log files.
+    pub dir: Option<String>,
+    /// Whether to write log files, besides the terminal.
+    pub files: Option<bool>,
+    /// Format of the messages, `text` or `json`.
+    pub format: Option<String>,
+    /// Whether to send the messages to syslog, or journald, instead of the
+    /// terminal.
+    pub syslog: Option<bool>,
+    /// Levels for specific targets, e.g. `benita = "debug"`.
+    #[serde(default)]
+    pub targets: BTreeMap<String, String>,
+    /// Rotation of each log file, by file name, e.g. `benita-commander.log`.
+    #[serde(default)]
+    pub rotation: BTreeMap<String, RotationConfig>,
+}
+
+/// Rotation settings of a log file. Missing ones take their default value.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RotationConfig {
+    /// Size that the file is rotated at, or no limit if `0`.
+    pub max_size_kb: Option<u64>,
+    /// Whether to rotate the file when the day changes.
+    pub daily: Option<bool>,
+    /// Old files kept.
+    pub keep: Option<usize>,
+    /// Whether to compress the old files with gzip.
+    pub compress: Option<bool>,
+}
+
+/// History settings of the interactive shells.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HistoryConfig {
+    /// Directory for the history files, one per kind of sensor and endpoint.
+    pub dir: Option<String>,
+    /// Lines kept in every history file.
+    pub max_size: Option<usize>,
+}
+
+/// Settings of a named sensor.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SensorConfig {
+    pub kind: String,
+    /// I2C bus, e.g. `/dev/i2c-1`.
+    pub path: Option<String>,
+    /// I2C address.
+    pub address: Option<u16>,
+    /// URL that the REP server binds to.
+    pub bind: Option<String>,
+    /// URL that REQ clients connect to.
+    pub connect: Option<String>,
+    /// Put the sensor to sleep when its REP server stops.
+    #[serde(default)]
+    pub sleep_on_exit: bool,
+    /// Consecutive requests failed on the sensor after which its REP server
+    /// stops.
+    pub max_errors: Option<u32>,
+    /// Pause after every command, in milliseconds.
+    pub delay_ms: Option<u64>,
+    /// How long REQ clients wait for a reply, in milliseconds.
+    pub timeout_ms: Option<i32>,
+    /// How many times REQ clients send a request again, when no reply arrives.
+    pub retries: Option<u32>,
+}
+
+impl Config {
+    /// Read the configuration from a TOML file.
+    pub fn from_file(path: &str) -> Result<Config> {
+        let mut contents = String::new();
+        let _read = File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .chain_err(|| ErrorKind::ConfigFile(path.to_string()))?;
+        Config::parse(&contents).chain_err(|| ErrorKind::ConfigFile(path.to_string()))
+    }
+
+    /// Parse the configuration from TOML.
+    pub fn parse(contents: &str) -> Result<Config> {
+        let config = toml::from_str(contents)?;
+        Ok(config)
+    }
+
+    /// Settings of the sensor called `name`.
+    pub fn sensor(&self, name: &str) -> Result<&SensorConfig> {
+        self.sensors
+            .get(name)
+            .ok_or_else(|| ErrorKind::UnknownSensorName(name.to_string()).into())
+    }
+
+    /// Settings of the sensor called `name`, which must be of the given kind.
+    pub fn sensor_of_kind(&self, name: &str, kind: SensorKind) -> Result<&SensorConfig> {
+        let sensor = self.sensor(name)?;
+        if sensor.kind()? != kind {
+            bail!(ErrorKind::WrongSensorKind(
+                name.to_string(),
+                kind.name().to_string()
+            ));
+        }
+        Ok(sensor)
+    }
+
+    /// REP servers for every sensor with a `bind` URL.
+    pub fn servers(&self) -> Result<Vec<ServerConfig>> {
+        self.sensors
+            .iter()
+            .filter(|&(_, sensor)| sensor.bind.is_some())
+            .map(|(name, sensor)| -> Result<ServerConfig> {
+                Ok(ServerConfig {
+                    kind: sensor.kind()?,
+                    path: resolve(None, sensor.path.as_ref(), &format!("{}.path", name))?,
+                    address: sensor
+                        .address
+                        .ok_or_else(|| ErrorKind::MissingSetting(format!("{}.address", name)))?,
+                    url: resolve(None, sensor.bind.as_ref(), &format!("{}.bind", name))?,
+                    sleep_on_exit: sensor.sleep_on_exit,
+                    max_errors: sensor.max_errors,
+                    delay_ms: sensor.delay_ms,
+                })
+            })
+            .collect()
+    }
+}
+
+impl SensorConfig {
+    pub fn kind(&self) -> Result<SensorKind> {
+        self.kind
+            .parse::<SensorKind>()
+            .map_err(|_| ErrorKind::UnknownSensorKind(self.kind.clone()).into())
+    }
+}
+
+/// The value given as an argument (from the command-line or the environment),
+/// falling back to the value from the configuration file.
+pub fn resolve(arg: Option<&str>, file: Option<&String>, setting: &str) -> Result<String> {
+    arg.map(|s| s.to_string())
+        .or_else(|| file.cloned())
+        .ok_or_else(|| ErrorKind::MissingSetting(setting.to_string()).into())
+}
+
+/// The I2C address given as an argument, falling back to the value from the
+/// configuration file.
+pub fn resolve_address(arg: Option<&str>, file: Option<u16>, setting: &str) -> Result<u16> {
+    match arg {
+        Some(addr) => addr
+            .parse()
+            .chain_err(|| ErrorKind::InvalidAddress(addr.to_string())),
+        None => file.ok_or_else(|| ErrorKind::MissingSetting(setting.to_string()).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        [logging]
+        level = "debug"
+
+        [sensors.tank-ph]
+        kind = "ph"
+        path = "/dev/i2c-1"
+        address = 99
+        bind = "tcp://*:7778"
+        connect = "tcp://127.0.0.1:7778"
+        sleep_on_exit = true
+        max_errors = 5
+
+        [sensors.tank-ec]
+        kind = "conductivity"
+        connect = "tcp://127.0.0.1:7777"
+    "#;
+
+    #[test]
+    fn parse_config_with_named_sensors() {
+        let config = Config::parse(CONFIG).unwrap();
+        assert_eq!(config.logging.unwrap().level, Some("debug".to_string()));
+        assert_eq!(config.sensors.len(), 2);
+        let sensor = config.sensor("tank-ph").unwrap();
+        assert_eq!(sensor.kind().unwrap(), SensorKind::Ph);
+        assert_eq!(sensor.address, Some(99));
+        assert!(config.sensor("tank-do").is_err());
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        assert!(Config::parse("[sensors.tank-ph]\nkind = \"ph\"\nadress = 99\n").is_err());
+        assert!(Config::parse("[sensors.tank-ph]\nkind = \"ph\"\nmax_error = 5\n").is_err());
+        assert!(Config::parse("[loging]\nlevel = \"debug\"\n").is_err());
+    }
+
+    #[test]
+    fn servers_are_the_sensors_with_bind_urls() {
+        let config = Config::parse(CONFIG).unwrap();
+        let servers = config.servers().unwrap();
+        assert_eq!(
+            servers,
+            vec![
+                ServerConfig {
+                    kind: SensorKind::Ph,
+                    path: "/dev/i2c-1".to_string(),
+                    address: 99,
+                    url: "tcp://*:7778".to_string(),
+                    sleep_on_exit: true,
+                    max_errors: Some(5),
+                    delay_ms: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn arguments_override_the_config_file() {
+        let file = Some("tcp://127.0.0.1:7778".to_string());
+        assert_eq!(
+            resolve(Some("tcp://10.0.0.1:7778"), file.as_ref(), "URL").unwrap(),
+            "tcp://10.0.0.1:7778"
+        );
+        assert_eq!(
+            resolve(None, file.as_ref(), "URL").unwrap(),
+            "tcp://127.0.0.1:7778"
+        );
+        assert!(resolve(None, None, "URL").is_err());
+        assert_eq!(
+            resolve_address(Some("98"), Some(99), "I2C_ADDRESS").unwrap(),
+            98
+        );
+        assert_eq!(resolve_address(None, Some(99), "I2C_ADDRESS").unwrap(), 99);
+        assert!(resolve_address(Some("ph"), Some(99), "I2C_ADDRESS").is_err());
+    }
+
+    #[test]
+    fn named_sensors_must_be_of_the_expected_kind() {
+        let config = Config::parse(CONFIG).unwrap();
+        assert!(config.sensor_of_kind("tank-ph", SensorKind::Ph).is_ok());
+        assert!(
+            config
+                .sensor_of_kind("tank-ph", SensorKind::Conductivity)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Multi-sensor console for the top-level interactive shell.
+use super::client::ClientConfig;
+use super::sensors::{SensorKind, SensorRequester};
+
+/// Command syntax understood by the console. Any other line is sent to the
+/// sensor currently in use.
+pub const COMMANDS: &[&str] = &[
+    "connect <sensor> <url>",
+    "disconnect <sensor>",
+    "sensors",
+    "use <sensor>",
+];
+
+/// A REQ client connected to a sensor's REP server.
+struct Connection {
+    kind: SensorKind,
+    url: String,
+    requester: Box<SensorRequester>,
+}
+
+/// Console that manages connections to several sensors, and routes commands
+/// to the one in use.
+pub struct Console {
+    connections: Vec<Connection>,
+    current: Option<SensorKind>,
+}
+
+impl Console {
+    pub fn new() -> Console {
+        Console {
+            connections: Vec::new(),
+            current: None,
+        }
+    }
+
+    /// Evaluate a line of user input. Returns a String.
+    pub fn eval(&mut self, line: &str) -> String {
+        let args: Vec<&str> = line.split_whitespace().collect();
+        match args.first().cloned() {
+            Some("connect") if args.len() == 3 => self.connect(args[1], args[2]),
+            Some("connect") => "usage: connect <sensor> <url>".to_string(),
+            Some("disconnect") if args.len() == 2 => self.disconnect(args[1]),
+            Some("disconnect") => "usage: disconnect <sensor>".to_string(),
+            Some("sensors") if args.len() == 1 => self.sensors(),
+            Some("use") if args.len() == 2 => self.use_sensor(args[1]),
+            Some("use") => "usage: use <sensor>".to_string(),
+            _ => self.forward(line),
+        }
+    }
+
+    fn connect(&mut self, sensor: &str, url: &str) -> String {
+        let kind = match sensor.parse::<SensorKind>() {
+            Ok(kind) => kind,
+            Err(e) => return e.to_string(),
+        };
+        let requester = match kind.requester(url, &ClientConfig::default()) {
+            Ok(requester) => requester,
+            Err(e) => return format!("could not connect to {}: {}", url, e),
+        };
+        self.connections.retain(|c| c.kind != kind);
+        self.connections.push(Connection {
+            kind,
+            url: url.to_string(),
+            requester,
+        });
+        if self.current.is_none() {
+            self.current = Some(kind);
+        }
+        format!("{} connected to {}", kind.name(), url)
+    }
+
+    fn disconnect(&mut self, sensor: &str) -> String {
+        let kind = match sensor.parse::<SensorKind>() {
+            Ok(kind) => kind,
+            Err(e) => return e.to_string(),
+        };
+        if !self.connections.iter().any(|c| c.kind == kind) {
+            return format!("{} is not connected", kind.name());
+        }
+        self.connections.retain(|c| c.kind != kind);
+        if self.current == Some(kind) {
+            self.current = None;
+        }
+        format!("{} disconnected", kind.name())
+    }
+
+    fn sensors(&self) -> String {
+        if self.connections.is_empty() {
+            return "no sensors connected".to_string();
+        }
+        self.connections
+            .iter()
+            .map(|c| {
+                let marker = if self.current == Some(c.kind) { "*" } else { " " };
+                format!("{} {} {}", marker, c.kind.name(), c.url)
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn use_sensor(&mut self, sensor: &str) -> String {
+        let kind = match sensor.parse::<SensorKind>() {
+            Ok(kind) => kind,
+            Err(e) => return e.to_string(),
+        };
+        if !self.connections.iter().any(|c| c.kind == kind) {
+            return format!("{} is not connected", kind.name());
+        }
+        self.current = Some(kind);
+        format!("using {}", kind.name())
+    }
+
+    fn forward(&self, line: &str) -> String {
+        let kind = match self.current {
+            Some(kind) => kind,
+            None => return "no sensor in use, try `connect <sensor> <url>`".to_string(),
+        };
+        let connection = self.connections
+            .iter()
+            .find(|c| c.kind == kind)
+            .expect("sensor in use must be connected");
+        match connection.requester.eval(line) {
+            Ok(response) => format!("{}: {}", kind.name(), response),
+            Err(e) => format!("{}: error: {}", kind.name(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn console_without_sensors_reports_nothing_connected() {
+        let mut console = Console::new();
+        assert_eq!(console.eval("sensors"), "no sensors connected");
+        assert_eq!(
+            console.eval("R"),
+            "no sensor in use, try `connect <sensor> <url>`"
+        );
+    }
+
+    #[test]
+    fn console_use_requires_a_connected_sensor() {
+        let mut console = Console::new();
+        assert_eq!(console.eval("use ph"), "ph is not connected");
+        assert_eq!(console.eval("disconnect ph"), "ph is not connected");
+    }
+
+    #[test]
+    fn console_rejects_unknown_sensors_and_bad_usage() {
+        let mut console = Console::new();
+        assert_eq!(console.eval("use orange"), "unknown sensor: orange");
+        assert_eq!(console.eval("connect ph"), "usage: connect <sensor> <url>");
+        assert_eq!(console.eval("use"), "usage: use <sensor>");
+    }
+}
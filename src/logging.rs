@@ -1,8 +1,24 @@
 //! Basic logging utilities.
+use std::cell::{Cell, RefCell};
+use std::cmp;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
 use chrono;
 use fern;
 use log;
+use serde_json;
 use std;
+use xdg;
+
+use super::config::{LoggingConfig, RotationConfig};
+use super::rotation::{Rotation, RotatingFile};
 
 // Errors and their descriptions
 mod errors {
@@ -12,58 +28,358 @@ mod errors {
                 description("could not find the log file")
                 display("could not find the log file: {}", path)
             }
+            LogFileFailed(path: String) {
+                description("could not write the log file")
+                display("could not write the log file: {}", path)
+            }
             LoggerFailed {
                 description("logger could not be started")
             }
+            InvalidLevel(level: String) {
+                description("invalid log level")
+                display("invalid log level: {}", level)
+            }
+            InvalidFormat(format: String) {
+                description("invalid log format")
+                display("invalid log format: {}", format)
+            }
+            SyslogFailed(path: String) {
+                description("could not connect to syslog")
+                display("could not connect to syslog: {}", path)
+            }
         }
     }
 }
 
 pub use self::errors::*;
 
+/// Socket of the syslog daemon, or of journald, which listens on it as well.
+pub const SYSLOG_SOCKET: &str = "/dev/log";
+
+/// Directory for the log files, when there is no home directory.
+pub const SYSTEM_LOG_DIR: &str = "/var/log/benita-commander";
+
+/// Formats of the log messages.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line, with the fields of `JsonLine`.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<LogFormat> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => bail!(ErrorKind::InvalidFormat(s.to_string())),
+        }
+    }
+}
+
+/// A request to a sensor, and the reply to it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Exchange {
+    pub request: String,
+    pub reply: String,
+    /// Time between receiving the request and sending the reply.
+    pub latency: Duration,
+}
+
+thread_local! {
+    // Kind of sensor that the messages of the thread are about.
+    static SENSOR: Cell<Option<&'static str>> = Cell::new(None);
+    // Exchange that the message being logged is about.
+    static EXCHANGE: RefCell<Option<Exchange>> = RefCell::new(None);
+}
+
+/// Set the kind of sensor that the messages of the current thread are about,
+/// e.g. for the thread of a REP server.
+pub fn set_sensor(kind: Option<&'static str>) {
+    SENSOR.with(|sensor| sensor.set(kind));
+}
+
+/// Log the reply to a request. JSON logs have the request, the reply, and the
+/// latency as fields.
+pub fn log_exchange(exchange: Exchange) {
+    let reply = exchange.reply.clone();
+    EXCHANGE.with(|current| *current.borrow_mut() = Some(exchange));
+    info!("REP: {}", reply);
+    EXCHANGE.with(|current| *current.borrow_mut() = None);
+}
+
+/// Fields of the log messages in JSON.
+#[derive(Debug, Serialize)]
+struct JsonLine<'a> {
+    /// UTC, in RFC 3339.
+    timestamp: String,
+    level: String,
+    target: &'a str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sensor: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_ms: Option<f64>,
+}
+
+/// Log message as a line of JSON.
+pub fn json_line(level: log::LogLevel, target: &str, message: &fmt::Arguments) -> String {
+    EXCHANGE.with(|exchange| {
+        let exchange = exchange.borrow();
+        let line = JsonLine {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            level: level.to_string(),
+            target,
+            message: message.to_string(),
+            sensor: SENSOR.with(|sensor| sensor.get()),
+            request: exchange.as_ref().map(|e| e.request.as_str()),
+            reply: exchange.as_ref().map(|e| e.reply.as_str()),
+            latency_ms: exchange.as_ref().map(|e| {
+                e.latency.as_secs() as f64 * 1000.0 + f64::from(e.latency.subsec_nanos()) / 1e6
+            }),
+        };
+        serde_json::to_string(&line).unwrap_or_else(|_| message.to_string())
+    })
+}
+
 /// Basic logger for shell terminals
-pub fn base_term_logger() -> Result<fern::Dispatch> {
-    let base_config = fern::Dispatch::new().format(|out, message, record| {
-        out.finish(format_args!(
+pub fn base_term_logger(format: LogFormat) -> Result<fern::Dispatch> {
+    let base_config = fern::Dispatch::new().format(move |out, message, record| match format {
+        LogFormat::Text => out.finish(format_args!(
             "[{}][{}] {}",
             record.target(),
             record.level(),
             message
-        ))
+        )),
+        LogFormat::Json => out.finish(format_args!(
+            "{}",
+            json_line(record.level(), record.target(), message)
+        )),
     });
     Ok(base_config)
 }
 
-/// Basic logger for file storage.
-pub fn base_file_logger(path: &str) -> Result<fern::Dispatch> {
-    let base_config = fern::Dispatch::new()
-        .format(|out, message, record| {
-            out.finish(format_args!(
+/// Basic logger for file storage, which rotates the file.
+pub fn base_file_logger(
+    path: &Path,
+    format: LogFormat,
+    rotation: Rotation,
+) -> Result<fern::Dispatch> {
+    let file_logger = FileLogger {
+        file: Mutex::new(RotatingFile::open(path, rotation)?),
+        format,
+    };
+    let base_config = fern::Dispatch::new().chain(Box::new(file_logger) as Box<log::Log>);
+    Ok(base_config)
+}
+
+/// Logger that writes the messages to a log file, which is rotated.
+pub struct FileLogger {
+    file: Mutex<RotatingFile>,
+    format: LogFormat,
+}
+
+impl log::Log for FileLogger {
+    fn enabled(&self, _metadata: &log::LogMetadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::LogRecord) {
+        let line = match self.format {
+            LogFormat::Text => format!(
                 "{}[{}][{}] {}",
                 chrono::Local::now().format("[%Y-%m-%d][%H:%M:%S]"),
                 record.target(),
                 record.level(),
-                message
-            ))
-        })
-        .chain(fern::log_file(path)
-            .chain_err(|| ErrorKind::LogFileNotFound(path.to_string()))?);
-    Ok(base_config)
+                record.args()
+            ),
+            LogFormat::Json => json_line(record.level(), record.target(), record.args()),
+        };
+        if let Ok(mut file) = self.file.lock() {
+            if let Err(e) = file.write_line(&line) {
+                // The logger can't log its own failures.
+                eprintln!("{}", e);
+            }
+        }
+    }
 }
 
-/// Configure and start logging.
-pub fn start_log() -> Result<()> {
-    let base_config = fern::Dispatch::new();
+/// Logger that sends the messages to syslog, or journald, over its socket.
+pub struct SyslogLogger {
+    socket: UnixDatagram,
+    format: LogFormat,
+}
 
-    let err_logger_file = base_file_logger("benita-commander.err")?.filter(|metadata| {
-        // Reject messages with the `Info` log level, accept others.
-        //
-        // This could be useful for sending Error messages to stderr
-        // and avoiding duplicate messages in stdout.
-        metadata.level() != log::LogLevelFilter::Info
-    });
+impl SyslogLogger {
+    pub fn new(path: &str, format: LogFormat) -> Result<SyslogLogger> {
+        let socket = UnixDatagram::unbound()
+            .and_then(|socket| socket.connect(path).map(|_| socket))
+            .chain_err(|| ErrorKind::SyslogFailed(path.to_string()))?;
+        Ok(SyslogLogger { socket, format })
+    }
+}
+
+impl log::Log for SyslogLogger {
+    fn enabled(&self, _metadata: &log::LogMetadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::LogRecord) {
+        let message = match self.format {
+            LogFormat::Text => format!("[{}] {}", record.target(), record.args()),
+            LogFormat::Json => json_line(record.level(), record.target(), record.args()),
+        };
+        let line = format!(
+            "<{}>benita-commander: {}",
+            syslog_priority(record.level()),
+            message
+        );
+        // There is nowhere else to report that the message was lost.
+        let _sent = self.socket.send(line.as_bytes());
+    }
+}
+
+/// Priority of syslog messages, with the `daemon` facility.
+pub fn syslog_priority(level: log::LogLevel) -> u8 {
+    const DAEMON: u8 = 3 << 3;
+    let severity = match level {
+        log::LogLevel::Error => 3,
+        log::LogLevel::Warn => 4,
+        log::LogLevel::Info => 6,
+        log::LogLevel::Debug | log::LogLevel::Trace => 7,
+    };
+    DAEMON + severity
+}
+
+/// Parse a log level, e.g. `info`.
+pub fn parse_level(level: &str) -> Result<log::LogLevelFilter> {
+    level
+        .parse()
+        .map_err(|_| ErrorKind::InvalidLevel(level.to_string()).into())
+}
+
+/// Settings of the logger, from the configuration file and the command-line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogSettings {
+    pub level: log::LogLevelFilter,
+    /// Levels for specific targets.
+    pub targets: Vec<(String, log::LogLevelFilter)>,
+    /// Directory for the log files.
+    pub dir: PathBuf,
+    /// Whether to write log files, besides the terminal.
+    pub files: bool,
+    pub format: LogFormat,
+    /// Whether to send the messages to syslog, instead of the terminal.
+    pub syslog: bool,
+    /// Rotation of specific log files, by file name.
+    pub rotation: BTreeMap<String, Rotation>,
+}
+
+impl LogSettings {
+    /// Settings from the `[logging]` section of the configuration file, with
+    /// defaults for the missing ones.
+    pub fn from_config(config: Option<&LoggingConfig>) -> Result<LogSettings> {
+        let mut settings = LogSettings {
+            level: log::LogLevelFilter::Info,
+            targets: Vec::new(),
+            dir: default_log_dir(),
+            files: true,
+            format: LogFormat::Text,
+            syslog: false,
+            rotation: BTreeMap::new(),
+        };
+        if let Some(config) = config {
+            if let Some(ref level) = config.level {
+                settings.level = parse_level(level)?;
+            }
+            for (target, level) in &config.targets {
+                settings.targets.push((target.clone(), parse_level(level)?));
+            }
+            if let Some(ref dir) = config.dir {
+                settings.dir = PathBuf::from(dir);
+            }
+            if let Some(files) = config.files {
+                settings.files = files;
+            }
+            if let Some(ref format) = config.format {
+                settings.format = format.parse()?;
+            }
+            if let Some(syslog) = config.syslog {
+                settings.syslog = syslog;
+            }
+            for (file, rotation) in &config.rotation {
+                settings.rotation.insert(file.clone(), rotation_from_config(rotation));
+            }
+        }
+        Ok(settings)
+    }
+
+    /// Rotation of the log file, or the default one.
+    pub fn rotation_of(&self, file: &str) -> Rotation {
+        self.rotation.get(file).cloned().unwrap_or_default()
+    }
+}
+
+/// Rotation from the settings of a log file, with defaults for the missing
+/// ones.
+pub fn rotation_from_config(config: &RotationConfig) -> Rotation {
+    let mut rotation = Rotation::default();
+    match config.max_size_kb {
+        Some(0) => rotation.max_size = None,
+        Some(kb) => rotation.max_size = Some(kb * 1024),
+        None => {}
+    }
+    if let Some(daily) = config.daily {
+        rotation.daily = daily;
+    }
+    if let Some(keep) = config.keep {
+        rotation.keep = keep;
+    }
+    if let Some(compress) = config.compress {
+        rotation.compress = compress;
+    }
+    rotation
+}
+
+/// Directory for the log files, under the XDG data directory, e.g.
+/// `~/.local/share/benita-commander/logs`, or `SYSTEM_LOG_DIR` without a
+/// home directory, e.g. for some system services.
+pub fn default_log_dir() -> PathBuf {
+    match xdg::BaseDirectories::with_prefix("benita-commander") {
+        Ok(dirs) => dirs.get_data_home().join("logs"),
+        Err(_) => PathBuf::from(SYSTEM_LOG_DIR),
+    }
+}
+
+/// The level, made more verbose by `verbosity` steps, or quieter if negative.
+pub fn adjust_level(level: log::LogLevelFilter, verbosity: i64) -> log::LogLevelFilter {
+    const LEVELS: [log::LogLevelFilter; 6] = [
+        log::LogLevelFilter::Off,
+        log::LogLevelFilter::Error,
+        log::LogLevelFilter::Warn,
+        log::LogLevelFilter::Info,
+        log::LogLevelFilter::Debug,
+        log::LogLevelFilter::Trace,
+    ];
+    let index = LEVELS.iter().position(|l| *l == level).unwrap_or(3) as i64;
+    let adjusted = cmp::max(0, cmp::min(index + verbosity, LEVELS.len() as i64 - 1));
+    LEVELS[adjusted as usize]
+}
 
-    let err_logger = base_term_logger()?
+/// Configure and start logging.
+pub fn start_log(settings: &LogSettings) -> Result<()> {
+    let mut base_config = fern::Dispatch::new().level(settings.level);
+    for &(ref target, level) in &settings.targets {
+        base_config = base_config.level_for(target.clone(), level);
+    }
+
+    let err_logger = base_term_logger(settings.format)?
         .level(log::LogLevelFilter::Trace)
         .filter(|metadata| {
             // Reject messages with the `Info` log level, accept others.
@@ -74,15 +390,7 @@ pub fn start_log() -> Result<()> {
         })
         .chain(std::io::stderr());
 
-    let info_logger_file = base_file_logger("benita-commander.log")?.filter(|metadata| {
-        // Reject messages with the `Info` log level, accept others.
-        //
-        // This could be useful for sending Error messages to stderr
-        // and avoiding duplicate messages in stdout.
-        metadata.level() == log::LogLevelFilter::Info
-    });
-
-    let term_logger = base_term_logger()?
+    let term_logger = base_term_logger(settings.format)?
         .level(log::LogLevelFilter::Info)
         .filter(|metadata| {
             // Reject messages with the `Info` log level, accept others.
@@ -91,14 +399,159 @@ pub fn start_log() -> Result<()> {
             // and avoiding duplicate messages in stdout.
             metadata.level() == log::LogLevelFilter::Info
         })
-        .chain(std::io::stdout());
-
-    let _start = base_config
-        .chain(err_logger_file)
-        .chain(err_logger)
-        .chain(info_logger_file)
-        .chain(term_logger)
-        .apply()
-        .chain_err(|| ErrorKind::LoggerFailed)?;
+        // Not stdout, which is left for the output of batch mode.
+        .chain(std::io::stderr());
+
+    if settings.syslog {
+        let syslog_logger = SyslogLogger::new(SYSLOG_SOCKET, settings.format)?;
+        base_config = base_config.chain(Box::new(syslog_logger) as Box<log::Log>);
+    } else {
+        base_config = base_config.chain(err_logger).chain(term_logger);
+    }
+    if settings.files {
+        match file_loggers(settings) {
+            Ok((err_logger_file, info_logger_file)) => {
+                base_config = base_config.chain(err_logger_file).chain(info_logger_file);
+            }
+            // The commands still work without log files, so they are not
+            // worth failing for.
+            Err(e) => eprintln!("warning: {}, logging without log files", e),
+        }
+    }
+
+    let _start = base_config.apply().chain_err(|| ErrorKind::LoggerFailed)?;
     Ok(())
 }
+
+/// Loggers for the `benita-commander.err` and `benita-commander.log` files in
+/// the log directory, which is created if missing.
+fn file_loggers(settings: &LogSettings) -> Result<(fern::Dispatch, fern::Dispatch)> {
+    let dir = &settings.dir;
+    let _dir = fs::create_dir_all(dir)
+        .chain_err(|| ErrorKind::LogFileNotFound(dir.display().to_string()))?;
+    let err_file = base_file_logger(
+        &dir.join("benita-commander.err"),
+        settings.format,
+        settings.rotation_of("benita-commander.err"),
+    )?;
+    let log_file = base_file_logger(
+        &dir.join("benita-commander.log"),
+        settings.format,
+        settings.rotation_of("benita-commander.log"),
+    )?;
+
+    let err_logger_file = err_file.filter(|metadata| {
+        // Reject messages with the `Info` log level, accept others.
+        //
+        // This could be useful for sending Error messages to stderr
+        // and avoiding duplicate messages in stdout.
+        metadata.level() != log::LogLevelFilter::Info
+    });
+
+    let info_logger_file = log_file.filter(|metadata| {
+        // Reject messages with the `Info` log level, accept others.
+        //
+        // This could be useful for sending Error messages to stderr
+        // and avoiding duplicate messages in stdout.
+        metadata.level() == log::LogLevelFilter::Info
+    });
+
+    Ok((err_logger_file, info_logger_file))
+}
+
+/// Stop logging, flushing and closing the log files.
+pub fn stop_log() {
+    if let Ok(logger) = log::shutdown_logger() {
+        drop(logger);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verbosity_adjusts_the_level_within_bounds() {
+        assert_eq!(adjust_level(log::LogLevelFilter::Info, 1), log::LogLevelFilter::Debug);
+        assert_eq!(adjust_level(log::LogLevelFilter::Info, 5), log::LogLevelFilter::Trace);
+        assert_eq!(adjust_level(log::LogLevelFilter::Info, -1), log::LogLevelFilter::Warn);
+        assert_eq!(adjust_level(log::LogLevelFilter::Warn, -5), log::LogLevelFilter::Off);
+    }
+
+    #[test]
+    fn settings_default_to_info_with_log_files() {
+        let settings = LogSettings::from_config(None).unwrap();
+        assert_eq!(settings.level, log::LogLevelFilter::Info);
+        assert!(settings.files);
+        let config = LoggingConfig {
+            level: Some("debug".to_string()),
+            dir: Some("/var/log/benita-commander".to_string()),
+            files: Some(false),
+            ..LoggingConfig::default()
+        };
+        let settings = LogSettings::from_config(Some(&config)).unwrap();
+        assert_eq!(settings.level, log::LogLevelFilter::Debug);
+        assert_eq!(settings.dir, PathBuf::from("/var/log/benita-commander"));
+        assert!(!settings.files);
+    }
+
+    #[test]
+    fn rotation_settings_override_the_defaults() {
+        let config = RotationConfig {
+            max_size_kb: Some(0),
+            keep: Some(3),
+            compress: Some(true),
+            ..RotationConfig::default()
+        };
+        let rotation = rotation_from_config(&config);
+        assert_eq!(rotation.max_size, None);
+        assert!(rotation.daily);
+        assert_eq!(rotation.keep, 3);
+        assert!(rotation.compress);
+
+        let mut settings = LogSettings::from_config(None).unwrap();
+        let _previous = settings.rotation.insert("benita-commander.err".to_string(), rotation);
+        assert_eq!(settings.rotation_of("benita-commander.err"), rotation);
+        assert_eq!(settings.rotation_of("benita-commander.log"), Rotation::default());
+    }
+
+    #[test]
+    fn json_lines_have_the_fields_of_the_exchange() {
+        set_sensor(Some("ph"));
+        EXCHANGE.with(|current| {
+            *current.borrow_mut() = Some(Exchange {
+                request: "R".to_string(),
+                reply: "7.02".to_string(),
+                latency: Duration::from_millis(905),
+            })
+        });
+        let line = json_line(
+            log::LogLevel::Info,
+            "benita_commander",
+            &format_args!("REP: 7.02"),
+        );
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["level"], "INFO");
+        assert_eq!(json["sensor"], "ph");
+        assert_eq!(json["request"], "R");
+        assert_eq!(json["reply"], "7.02");
+        assert_eq!(json["latency_ms"], 905.0);
+        assert!(json["timestamp"].as_str().unwrap().ends_with("+00:00"));
+
+        set_sensor(None);
+        EXCHANGE.with(|current| *current.borrow_mut() = None);
+        let line = json_line(
+            log::LogLevel::Warn,
+            "benita_commander",
+            &format_args!("retrying"),
+        );
+        assert!(!line.contains("sensor") && !line.contains("latency_ms"));
+    }
+
+    #[test]
+    fn syslog_priorities_use_the_daemon_facility() {
+        assert_eq!(syslog_priority(log::LogLevel::Error), 27);
+        assert_eq!(syslog_priority(log::LogLevel::Info), 30);
+        assert_eq!(syslog_priority(log::LogLevel::Trace), 31);
+    }
+}
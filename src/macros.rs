@@ -3,88 +3,205 @@
 /// Creates a Requester capable of:
 ///
 /// * parsing command-line input from a list of known requests
-/// * and, evaluating.
+/// * and, evaluating, retrying with a new socket when no reply arrives in time.
 macro_rules! requester {
-    ( $name:ident , $doc:tt ,  $req:ty,  $req_expr:ident , [ $( $request:ty ),* ] ) => {
+    ( $name:ident , $doc:tt , [ $( $request:ty ),* ] ) => {
         #[ doc = $doc ]
         pub struct $name {
-            pub requester: $req,
+            client: ::client::Client,
         }
 
         impl $name {
             pub fn new(url: &str) -> Result<$name> {
-                let req_socket =
-                    create_and_connect_requester(url).chain_err(|| "unable to setup req_socket")?;
-                let requester = $req_expr::new(req_socket)
-                    .chain_err(|| "requester artifact failed to start")?;
-                let _connect = requester
-                    .connect(url)
+                $name::with_config(url, &::client::ClientConfig::default())
+            }
+
+            pub fn with_config(url: &str, config: &::client::ClientConfig) -> Result<$name> {
+                let client = ::client::Client::connect(url, config)
                     .chain_err(|| "requester failed to connect")?;
 
-                Ok($name { requester })
+                Ok($name { client })
             }
 
-            req_fn_eval! { [ $( $request ),* ] }
+            recognizes_fn! { [ $( $request ),* ] }
+        }
+
+        impl ::sensors::SensorRequester for $name {
+            fn spec(&self) -> &'static ::sensors::SensorSpec {
+                &SPEC
+            }
+
+            fn url(&self) -> &str {
+                self.client.url()
+            }
+
+            fn eval(&self, s: &str) -> ::errors::Result<String> {
+                if !$name::recognizes(s) {
+                    bail!(SPEC.unrecognized(s));
+                }
+                debug!("evaluating: {:?}", s);
+                self.client.request(s)
+            }
         }
     };
 }
 
-macro_rules! req_fn_eval {
+macro_rules! recognizes_fn {
     ( [ $( $request:ty ),* ] ) => {
-        pub fn eval(&self, s: &str) -> Result<String> {
-            debug!("evaluating: {:?}", s);
-            $(
-                if let Ok(req) = s.parse::<$request>() {
-                    let rep = SocketRequest::send(&req, &self.requester)
-                        .chain_err(|| "bad REQ eval")?;
-                    return Ok(SocketReply::to_string(&rep));
-                } )*
-            Ok("command not recognized".to_string())
+        // Whether any of the known requests parses the command.
+        pub fn recognizes(s: &str) -> bool {
+            false $( || <$request as SocketRequest>::from_str(s).is_ok() )*
         }
     };
 }
 
 /// Creates a Responder capable of evaluating a list of explicit requests (commands).
 macro_rules! responder {
-    ( $name:ident , $doc:tt ,  $sensor:ident, $res:ty,  $res_expr:ident ,
-      [ $( $request:ident ),* ] ) => {
+    ( $name:ident , $doc:tt ,  $sensor:ident, [ $( $request:ident ),* ] ) => {
         #[ doc = $doc ]
         pub struct $name {
-            pub responder: $res,
+            socket: ::zmq::Socket,
+            pub sensor: $sensor,
         }
 
         impl $name {
             pub fn new(url: &str, path: &str, addr: u16) -> Result<$name> {
-                let req_socket =
-                    create_and_bind_responder(url).chain_err(|| "unable to setup req_socket")?;
+                let socket = ::server::bind_responder(url)
+                    .chain_err(|| "unable to setup the REP socket")?;
                 let sensor = $sensor::new(path, addr)
                     .chain_err(|| "could not start sensor")?;
-                let responder = $res_expr::new(req_socket, sensor)
-                    .chain_err(|| "responder artifact failed to start")?;
-                let _connect = responder
-                    .connect(url)
-                    .chain_err(|| "responder failed to connect")?;
 
-                Ok($name { responder })
+                Ok($name { socket, sensor })
+            }
+
+            recognizes_fn! { [ $( $request ),* ] }
+
+            eval_fn! { "bad REQ eval", [ $( $request ),* ] }
+        }
+
+        impl ::sensors::SensorResponder for $name {
+            fn spec(&self) -> &'static ::sensors::SensorSpec {
+                &SPEC
             }
 
-            res_fn_eval! { [ $( $request ),* ] }
+            fn recv(&self) -> ::errors::Result<Option<String>> {
+                ::server::recv_request(&self.socket)
+            }
+
+            fn send(&self, reply: &str) -> ::errors::Result<()> {
+                ::server::send_reply(&self.socket, reply)
+            }
+
+            fn eval(&self, s: &str) -> ::errors::Result<String> {
+                if !$name::recognizes(s) {
+                    bail!(SPEC.unrecognized(s));
+                }
+                $name::eval(self, s)
+            }
         }
     };
 }
 
-macro_rules! res_fn_eval {
-    ( [ $( $request:ident ),* ] ) => {
-        // Evaluate a conductivity command using the given responder. Returns a String.
-        pub fn eval(&self, s: &str) -> Result<String> {
+macro_rules! eval_fn {
+    ( $failed:tt , [ $( $request:ident ),* ] ) => {
+        // Evaluate a command on the sensor, with the first request that parses
+        // it. Returns the reply as a String.
+        pub fn eval(&self, s: &str) -> ::errors::Result<String> {
             debug!("evaluating: {:?}", s);
             $(
                 if let Ok(req) = <$request as SocketRequest>::from_str(s) {
-                    let rep = <$request as I2CCommand>::write(&req, &self.responder.sensor)
-                        .chain_err(|| "bad REQ eval")?;
+                    let rep = <$request as I2CCommand>::write(&req, &self.sensor)
+                        .chain_err(|| $failed)?;
                     return Ok(I2CResponse::to_string(&rep));
                 } )*
-            Ok("command not recognized".to_string())
+            bail!(SPEC.unrecognized(s))
+        }
+    };
+}
+
+/// Creates a Device handler that writes a list of explicit requests (commands)
+/// directly to the sensor over I2C.
+macro_rules! device {
+    ( $name:ident , $doc:tt ,  $sensor:ident, [ $( $request:ident ),* ] ) => {
+        #[ doc = $doc ]
+        pub struct $name {
+            pub sensor: $sensor,
+        }
+
+        impl $name {
+            pub fn new(path: &str, addr: u16) -> Result<$name> {
+                let sensor = $sensor::new(path, addr)
+                    .chain_err(|| "could not start sensor")?;
+
+                Ok($name { sensor })
+            }
+
+            recognizes_fn! { [ $( $request ),* ] }
+
+            eval_fn! { "bad I2C eval", [ $( $request ),* ] }
+        }
+
+        impl ::sensors::SensorDevice for $name {
+            fn spec(&self) -> &'static ::sensors::SensorSpec {
+                &SPEC
+            }
+
+            fn eval(&self, s: &str) -> ::errors::Result<String> {
+                if !$name::recognizes(s) {
+                    bail!(SPEC.unrecognized(s));
+                }
+                match $name::eval(self, s) {
+                    Ok(reply) => Ok(reply),
+                    Err(e) => {
+                        let reason = e.iter()
+                            .map(|e| e.to_string())
+                            .collect::<Vec<String>>()
+                            .join(": ");
+                        let kind = ::errors::ErrorKind::DeviceFailed(reason);
+                        Err(::errors::Error::with_chain(e, kind))
+                    }
+                }
+            }
         }
     };
 }
+
+/// Creates the static description of a sensor, deriving the names of its
+/// environment variables from `$env_prefix`.
+macro_rules! sensor_spec {
+    ( $spec:ident , $kind:expr , $name:tt , $about:tt , $env_prefix:tt , $units:tt ,
+      $commands:expr ) => {
+        pub static $spec: SensorSpec = SensorSpec {
+            kind: $kind,
+            name: $name,
+            about: $about,
+            units: $units,
+            env_prefix: $env_prefix,
+            req_url_env: concat!($env_prefix, "_REQ_URL"),
+            rep_url_env: concat!($env_prefix, "_REP_URL"),
+            rep_path_env: concat!($env_prefix, "_REP_PATH"),
+            rep_address_env: concat!($env_prefix, "_REP_ADDRESS"),
+            commands: $commands,
+        };
+    };
+}
+
+/// Creates the description of a sensor, with the command syntax of its
+/// requests, and its REQ client, REP server, and I2C device handler, which
+/// handle the same requests.
+macro_rules! sensor {
+    ( $sensor:ident , $kind:expr , $name:tt , $about:tt , $env_prefix:tt , $units:tt ,
+      $req:ident : $req_doc:tt , $rep:ident : $rep_doc:tt , $dev:ident : $dev_doc:tt ,
+      [ $( $request:ident => $syntax:tt ),* ] ) => {
+        /// Command syntax understood by the sensor, in the order of its requests.
+        pub const COMMANDS: &[&str] = &[ $( $syntax ),* ];
+
+        sensor_spec! { SPEC, $kind, $name, $about, $env_prefix, $units, COMMANDS }
+
+        requester! { $req, $req_doc, [ $( $request ),* ] }
+
+        responder! { $rep, $rep_doc, $sensor, [ $( $request ),* ] }
+
+        device! { $dev, $dev_doc, $sensor, [ $( $request ),* ] }
+    };
+}
@@ -4,23 +4,44 @@
 extern crate benita;
 extern crate chrono;
 extern crate clap;
+extern crate ctrlc;
 #[macro_use]
 extern crate error_chain;
 extern crate fern;
+extern crate flate2;
+#[cfg(test)]
+extern crate libc;
 #[macro_use]
 extern crate log;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
 extern crate rustyline;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate toml;
+extern crate xdg;
+extern crate zmq;
 
 #[macro_use]
 mod macros;
 
+pub mod backup;
+pub mod calibration;
 pub mod cli;
+pub mod client;
+pub mod completion;
+pub mod config;
+pub mod console;
 pub mod errors {
     //! Errors and their descriptions
     use super::conductivity;
+    use super::config;
     use super::logging;
     use super::ph;
     use super::readline;
+    use super::status::Status;
     use super::temperature;
     use benita;
 
@@ -29,11 +50,44 @@ pub mod errors {
             InvalidArgs {
                 description ("invalid command-line arguments")
             }
+            UnknownSensor(name: String) {
+                description ("unknown sensor")
+                display ("unknown sensor: {}", name)
+            }
+            ServersFailed(count: usize) {
+                description ("REP servers failed")
+                display ("{} REP server(s) failed", count)
+            }
+            UnknownCommand(cmd: String) {
+                description ("unknown command")
+                display ("unknown command: {}", cmd)
+            }
+            InvalidCommand(cmd: String, syntax: String) {
+                description ("invalid command")
+                display ("invalid command: {}, expected: {}", cmd, syntax)
+            }
+            TransportFailed(url: String) {
+                description ("no reply from the REP server")
+                display ("no reply from the REP server at {}", url)
+            }
+            DeviceFailed(reason: String) {
+                description ("the sensor failed")
+                display ("the sensor failed: {}", reason)
+            }
+            CommandsFailed(count: usize, status: Status) {
+                description ("commands failed")
+                display ("{} command(s) failed, the most severe with: {}", count, status)
+            }
+            UnsupportedFeature(feature: String) {
+                description ("feature is not supported by this build")
+                display ("enable the `{}` feature to use it", feature)
+            }
         }
         links {
             Benita(benita::errors::Error, benita::errors::ErrorKind);
             BenitaEzo(benita::ezo::errors::Error, benita::ezo::errors::ErrorKind);
             Conductivity(conductivity::Error, conductivity::ErrorKind);
+            Config(config::Error, config::ErrorKind);
             Logs(logging::Error, logging::ErrorKind);
             Ph(ph::Error, ph::ErrorKind);
             Readline(readline::Error, readline::ErrorKind);
@@ -45,25 +99,43 @@ pub mod errors {
 pub mod conductivity;
 pub mod ph;
 pub mod logging;
+pub mod output;
+pub mod pacing;
 pub mod readline;
+pub mod record;
+pub mod rotation;
+pub mod sensors;
+pub mod server;
+pub mod status;
 pub mod temperature;
-
-use std::thread;
-use std::time::Duration;
+pub mod watch;
 
 use self::errors::*;
+use self::backup::Backup;
 use self::cli::BenitaCommanderApp;
-use self::conductivity::{ConductivityREP, ConductivityREQ};
-//use self::logging::start_log;
-use self::ph::{PhREP, PhREQ};
-use self::readline::{CommanderReadline, SensorKind};
-use self::temperature::{TemperatureREP, TemperatureREQ};
+use self::client::ClientConfig;
+use self::config::{resolve, resolve_address, Config, SensorConfig};
+use self::console::Console;
+use self::logging::{adjust_level, parse_level, start_log, stop_log, LogSettings};
+use self::output::{Output, OutputFormat, Record};
+use self::pacing::Pacer;
+use self::readline::{default_history_dir, CommanderReadline, History, DEFAULT_HISTORY_SIZE};
+use self::record::{CsvSink, RecordedSensor, Sink};
+use self::sensors::{SensorKind, SensorRequester};
+use self::server::{ServerConfig, Shutdown};
+use self::status::Status;
+use self::watch::{parse_interval, WatchConfig};
+
+use std::cmp;
+use std::path::PathBuf;
+use std::process;
+use std::str::FromStr;
 
-use benita::ezo::network::Endpoint;
 use clap::ArgMatches;
+use error_chain::ChainedError;
 
 /// Execute the program from the given command-line arguments
-fn evaluate(args: &ArgMatches) -> Result<()> {
+fn evaluate(args: &ArgMatches, config: &Config) -> Result<()> {
     // This runs the whole program. Everything has brought us to this point.
     // Use it wisely.
     debug!(target: "benita-commander", "{:?}", args);
@@ -74,147 +146,350 @@ fn evaluate(args: &ArgMatches) -> Result<()> {
             println!("********************");
             println!("* benita-commander *");
             println!("********************");
-            CommanderReadline::Main.start(">> ", |s| s.to_string().to_uppercase())?
-        }
-        ("conductivity", _args) => {
-            debug!("conductivity readline");
-            match _args {
-                Some(m) => match m.subcommand() {
-                    ("rep", Some(rep_args)) => {
-                        let url = rep_args.value_of("URL").unwrap();
-                        let path = rep_args.value_of("I2C_PATH").unwrap();
-                        let addr = rep_args.value_of("I2C_ADDRESS").unwrap().parse().unwrap();
-
-                        let server = ConductivityREP::new(url, path, addr)?;
-                        loop {
-                            let req_str = &server.responder.recv()?;
-                            info!("REQ: {}", &req_str);
-                            let call: String = server.eval(&req_str)?;
-                            info!("REP: {}", &call);
-                            let _reply = &server.responder.send(call.as_bytes())?;
-
-                            thread::sleep(Duration::from_millis(400));
-                        }
-                    }
-                    ("req", Some(req_args)) => {
-                        let url = req_args.value_of("URL").unwrap();
-
-                        let requester = ConductivityREQ::new(url)?;
-
-                        let _exec = match req_args.values_of("cmds") {
-                            Some(cmds) => for cmd in cmds {
-                                let response = match requester.eval(cmd) {
-                                    Ok(parsed) => parsed,
-                                    _ => "command not in custom api".to_string(),
-                                };
-                                println!("REP: {}", response);
-                            },
-                            None => CommanderReadline::MainSensor(SensorKind::Conductivity)
-                                .start("conductivity>> ", |s| match requester.eval(s) {
-                                    Ok(response) => response,
-                                    _ => "command not in custom api".to_string(),
-                                })?,
-                        };
-                    }
-                    _ => println!("{}", m.usage()),
-                },
-                None => {}
-            }
+            let mut console = Console::new();
+            let shell = CommanderReadline::Main;
+            let history = shell_history(args, config, &shell, None);
+            shell.start(&history, |s| console.eval(s))?
         }
-        ("ph", _args) => {
-            debug!(target: "benita-commander", "ph readline");
-            match _args {
-                Some(m) => match m.subcommand() {
-                    ("rep", Some(rep_args)) => {
-                        let url = rep_args.value_of("URL").unwrap();
-                        let path = rep_args.value_of("I2C_PATH").unwrap();
-                        let addr = rep_args.value_of("I2C_ADDRESS").unwrap().parse().unwrap();
-
-                        let server = PhREP::new(url, path, addr)?;
-                        loop {
-                            let req_str = &server.responder.recv()?;
-                            info!("REQ: {}", &req_str);
-                            let call: String = server.eval(&req_str)?;
-                            info!("REP: {}", &call);
-                            let _reply = &server.responder.send(call.as_bytes())?;
-
-                            thread::sleep(Duration::from_millis(400));
-                        }
-                    }
-                    // REQ Client that connects to the given URL, capable of
-                    // interpreting from a list of known commands.
-                    ("req", Some(req_args)) => {
-                        let url = req_args.value_of("URL").unwrap();
-
-                        let requester = PhREQ::new(url)?;
-
-                        let _exec = match req_args.values_of("cmds") {
-                            Some(cmds) => for cmd in cmds {
-                                let response = match requester.eval(cmd) {
-                                    Ok(parsed) => parsed,
-                                    _ => "command not in custom api".to_string(),
-                                };
-                                println!("REP: {}", response);
-                            },
-                            None => CommanderReadline::MainSensor(SensorKind::Ph).start(
-                                "ph>> ",
-                                |s| match requester.eval(s) {
-                                    Ok(response) => response,
-                                    _ => "command not in custom api".to_string(),
-                                },
-                            )?,
-                        };
-                    }
-                    _ => println!("{}", m.usage()),
-                },
-                None => {}
+        ("serve", Some(m)) => {
+            debug!(target: "benita-commander", "serve");
+            let sleep_on_exit = m.is_present("sleep-on-exit");
+            let max_errors = parse_arg(m, "max-errors")?;
+            let delay_ms = parse_arg(m, "delay")?;
+            let mut servers = match m.values_of("sensor") {
+                Some(values) => values
+                    .collect::<Vec<&str>>()
+                    .chunks(4)
+                    .map(|v| -> Result<ServerConfig> {
+                        Ok(ServerConfig {
+                            kind: v[0].parse()?,
+                            path: v[1].to_string(),
+                            address: resolve_address(Some(v[2]), None, "I2C_ADDRESS")?,
+                            url: v[3].to_string(),
+                            sleep_on_exit,
+                            max_errors,
+                            delay_ms,
+                        })
+                    })
+                    .collect::<Result<Vec<ServerConfig>>>()?,
+                None => config.servers()?,
+            };
+            if servers.is_empty() {
+                bail!("no sensors to serve, use `-s` or a configuration file");
+            }
+            for server in &mut servers {
+                server.sleep_on_exit |= sleep_on_exit;
+                server.max_errors = max_errors.or(server.max_errors);
+                server.delay_ms = delay_ms.or(server.delay_ms);
             }
+
+            let shutdown = Shutdown::new();
+            shutdown.on_signals()?;
+            server::supervise(servers, &shutdown)?
         }
-        ("temperature", _args) => {
-            debug!(target: "benita-commander", "temperature readline");
-            match _args {
-                Some(m) => match m.subcommand() {
-                    ("rep", Some(rep_args)) => {
-                        let url = rep_args.value_of("URL").unwrap();
-                        let path = rep_args.value_of("I2C_PATH").unwrap();
-                        let addr = rep_args.value_of("I2C_ADDRESS").unwrap().parse().unwrap();
-
-                        let server = TemperatureREP::new(url, path, addr)?;
-                        loop {
-                            let req_str = &server.responder.recv()?;
-                            info!("REQ: {}", &req_str);
-                            let call: String = server.eval(&req_str)?;
-                            info!("REP: {}", &call);
-                            let _reply = &server.responder.send(call.as_bytes())?;
-
-                            thread::sleep(Duration::from_millis(400));
-                        }
-                    }
-                    ("req", Some(req_args)) => {
-                        let url = req_args.value_of("URL").unwrap();
-
-                        let requester = TemperatureREQ::new(url)?;
-
-                        let _exec =
-                            match req_args.values_of("cmds") {
-                                Some(cmds) => for cmd in cmds {
-                                    let response = match requester.eval(cmd) {
-                                        Ok(parsed) => parsed,
-                                        _ => "command not in custom api".to_string(),
-                                    };
-                                    println!("REP: {}", response);
-                                },
-                                None => CommanderReadline::MainSensor(SensorKind::Temperature)
-                                    .start("ph>> ", |s| match requester.eval(s) {
-                                        Ok(response) => response,
-                                        _ => "command not in custom api".to_string(),
-                                    })?,
-                            };
-                    }
-                    _ => println!("{}", m.usage()),
+        ("watch", Some(m)) => {
+            debug!(target: "benita-commander", "watch");
+            let mut sensors = match m.values_of("sensor") {
+                Some(values) => values
+                    .collect::<Vec<&str>>()
+                    .chunks(2)
+                    .map(|v| -> Result<(SensorKind, String)> {
+                        Ok((v[0].parse()?, v[1].to_string()))
+                    })
+                    .collect::<Result<Vec<(SensorKind, String)>>>()?,
+                None => Vec::new(),
+            };
+            for name in m.values_of("name").into_iter().flat_map(|names| names) {
+                let sensor = config.sensor(name)?;
+                let url = resolve(None, sensor.connect.as_ref(), &format!("{}.connect", name))?;
+                sensors.push((sensor.kind()?, url));
+            }
+            if sensors.is_empty() {
+                bail!("no sensors to watch, use `-s` or `-n`");
+            }
+
+            let compensation = match m.value_of("compensate-name") {
+                Some(name) => {
+                    let sensor = config.sensor_of_kind(name, SensorKind::Temperature)?;
+                    Some(resolve(None, sensor.connect.as_ref(), &format!("{}.connect", name))?)
+                }
+                None => m.value_of("compensate").map(|url| url.to_string()),
+            };
+
+            let watch_config = WatchConfig {
+                sensors,
+                command: m.value_of("command").unwrap_or("R").to_string(),
+                interval: parse_interval(m.value_of("interval").unwrap_or("5s"))?,
+                count: parse_arg(m, "count")?,
+                format: output_format(m)?,
+                client: ClientConfig {
+                    timeout_ms: parse_arg(m, "timeout")?.unwrap_or(client::DEFAULT_TIMEOUT_MS),
+                    retries: parse_arg(m, "retries")?.unwrap_or(client::DEFAULT_RETRIES),
                 },
-                None => {}
+                compensation,
+            };
+
+            let shutdown = Shutdown::new();
+            shutdown.on_signals()?;
+            watch::watch(&watch_config, &shutdown)?
+        }
+        ("record", Some(m)) => {
+            debug!(target: "benita-commander", "record");
+            let client_config = ClientConfig {
+                timeout_ms: parse_arg(m, "timeout")?.unwrap_or(client::DEFAULT_TIMEOUT_MS),
+                retries: parse_arg(m, "retries")?.unwrap_or(client::DEFAULT_RETRIES),
+            };
+            let names = match m.values_of("name") {
+                Some(names) => names.map(|name| name.to_string()).collect(),
+                None => config.sensors.keys().cloned().collect::<Vec<String>>(),
+            };
+            let sensors = names
+                .iter()
+                .map(|name| -> Result<RecordedSensor> {
+                    let sensor = config.sensor(name)?;
+                    RecordedSensor::new(name, sensor, m.is_present("direct"), &client_config)
+                })
+                .collect::<Result<Vec<RecordedSensor>>>()?;
+            if sensors.is_empty() {
+                bail!("no sensors to record, add them to the configuration file");
             }
+
+            let mut sink = record_sink(m)?;
+            let interval = parse_interval(m.value_of("interval").unwrap_or("1m"))?;
+            let shutdown = Shutdown::new();
+            shutdown.on_signals()?;
+            record::record(
+                &sensors,
+                &mut *sink,
+                interval,
+                parse_arg(m, "count")?,
+                &shutdown,
+            )?
+        }
+        (name, Some(m)) => {
+            let kind = name.parse::<SensorKind>()?;
+            debug!(target: "benita-commander", "{} readline", kind.name());
+            evaluate_sensor(kind, m, config)?
+        }
+        _ => println!("{}", args.usage()),
+    };
+
+    Ok(())
+}
+
+/// Storage of the readings of the `record` subcommand.
+fn record_sink(args: &ArgMatches) -> Result<Box<Sink>> {
+    match args.value_of("sqlite") {
+        #[cfg(feature = "sqlite")]
+        Some(path) => Ok(Box::new(record::SqliteSink::new(path)?)),
+        #[cfg(not(feature = "sqlite"))]
+        Some(_) => bail!(ErrorKind::UnsupportedFeature("sqlite".to_string())),
+        None => Ok(Box::new(CsvSink::new(args.value_of("csv-dir").unwrap_or("."))?)),
+    }
+}
+
+/// Settings of the sensor named with `--name` in the configuration file.
+fn named_sensor<'c>(
+    kind: SensorKind,
+    args: &ArgMatches,
+    config: &'c Config,
+) -> Result<Option<&'c SensorConfig>> {
+    match args.value_of("name") {
+        Some(name) => Ok(Some(config.sensor_of_kind(name, kind)?)),
+        None => Ok(None),
+    }
+}
+
+/// Evaluate the commands in batch mode, printing their outcomes to `output`.
+/// Fails with the most severe status of the failed commands, if any.
+fn eval_batch<'a, I, F>(
+    kind: SensorKind,
+    cmds: I,
+    output: &mut Output,
+    fail_fast: bool,
+    mut eval: F,
+) -> Result<()>
+where
+    I: Iterator<Item = &'a str>,
+    F: FnMut(&str) -> Result<String>,
+{
+    let mut failed = 0;
+    let mut worst = Status::Ok;
+    for cmd in cmds {
+        let result = eval(cmd);
+        let status = Status::of(&result);
+        output.print(&Record::new(kind, cmd, &result))?;
+        if status != Status::Ok {
+            failed += 1;
+        }
+        worst = cmp::max(worst, status);
+        // The rest of the commands would not be answered either.
+        if status == Status::TransportFailed || (fail_fast && status != Status::Ok) {
+            break;
+        }
+    }
+    if failed > 0 {
+        bail!(ErrorKind::CommandsFailed(failed, worst));
+    }
+    Ok(())
+}
+
+/// Format of the output of batch mode, from `--output`.
+fn output_format(args: &ArgMatches) -> Result<OutputFormat> {
+    Ok(parse_arg(args, "output")?.unwrap_or(OutputFormat::Text))
+}
+
+/// Text shown in interactive mode for the result of a command.
+fn describe_result(result: Result<String>) -> String {
+    match result {
+        Ok(response) => response,
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+/// Parse the value of an optional argument, e.g. `--max-errors`.
+fn parse_arg<T: FromStr>(args: &ArgMatches, name: &str) -> Result<Option<T>> {
+    match args.value_of(name) {
+        Some(value) => Ok(Some(value.parse().map_err(|_| ErrorKind::InvalidArgs)?)),
+        None => Ok(None),
+    }
+}
+
+/// REQ client for the URL given with `URL`, or in the configuration file.
+fn connect_requester(
+    kind: SensorKind,
+    args: &ArgMatches,
+    config: &Config,
+) -> Result<Box<SensorRequester>> {
+    let named = named_sensor(kind, args, config)?;
+    let url = resolve(
+        args.value_of("URL"),
+        named.and_then(|s| s.connect.as_ref()),
+        "URL",
+    )?;
+
+    let client_config = ClientConfig {
+        timeout_ms: parse_arg(args, "timeout")?
+            .or_else(|| named.and_then(|s| s.timeout_ms))
+            .unwrap_or(client::DEFAULT_TIMEOUT_MS),
+        retries: parse_arg(args, "retries")?
+            .or_else(|| named.and_then(|s| s.retries))
+            .unwrap_or(client::DEFAULT_RETRIES),
+    };
+
+    kind.requester(&url, &client_config)
+}
+
+/// Execute the `rep`, `req`, or `sensor` subcommands for the given kind of sensor.
+fn evaluate_sensor(kind: SensorKind, args: &ArgMatches, config: &Config) -> Result<()> {
+    let _eval = match args.subcommand() {
+        ("rep", Some(rep_args)) => {
+            let named = named_sensor(kind, rep_args, config)?;
+            let server_config = ServerConfig {
+                kind,
+                url: resolve(
+                    rep_args.value_of("URL"),
+                    named.and_then(|s| s.bind.as_ref()),
+                    "URL",
+                )?,
+                path: resolve(
+                    rep_args.value_of("I2C_PATH"),
+                    named.and_then(|s| s.path.as_ref()),
+                    "I2C_PATH",
+                )?,
+                address: resolve_address(
+                    rep_args.value_of("I2C_ADDRESS"),
+                    named.and_then(|s| s.address),
+                    "I2C_ADDRESS",
+                )?,
+                sleep_on_exit: rep_args.is_present("sleep-on-exit")
+                    || named.map_or(false, |s| s.sleep_on_exit),
+                max_errors: parse_arg(rep_args, "max-errors")?
+                    .or_else(|| named.and_then(|s| s.max_errors)),
+                delay_ms: parse_arg(rep_args, "delay")?
+                    .or_else(|| named.and_then(|s| s.delay_ms)),
+            };
+
+            let shutdown = Shutdown::new();
+            shutdown.on_signals()?;
+            server::serve(&server_config, &shutdown)?
+        }
+        // REQ Client that connects to the given URL, capable of
+        // interpreting from a list of known commands.
+        ("req", Some(req_args)) => {
+            let requester = connect_requester(kind, req_args, config)?;
+
+            let fail_fast = req_args.is_present("fail-fast");
+            let mut output = Output::new(output_format(req_args)?, "REP");
+            let _exec = match req_args.values_of("cmds") {
+                Some(cmds) => {
+                    eval_batch(kind, cmds, &mut output, fail_fast, |s| requester.eval(s))?
+                }
+                None => {
+                    let shell = CommanderReadline::Socket(kind);
+                    let history = shell_history(args, config, &shell, Some(requester.url()));
+                    shell.start(&history, |s| describe_result(requester.eval(s)))?
+                }
+            };
+        }
+        // Guided calibration, through the REP server of the sensor.
+        ("calibrate", Some(cal_args)) => {
+            let requester = connect_requester(kind, cal_args, config)?;
+            calibration::calibrate(&*requester)?
+        }
+        // Export of the calibration of the sensor, to a file.
+        ("backup", Some(backup_args)) => {
+            let requester = connect_requester(kind, backup_args, config)?;
+            let path = backup_args.value_of("file").unwrap();
+            let backup = backup::backup(&*requester)?;
+            backup.to_file(path)?;
+            println!(
+                "{} calibration string(s) saved to {}",
+                backup.calibration.len(),
+                path
+            );
+        }
+        // Import of the calibration of the sensor, from a file.
+        ("restore", Some(restore_args)) => {
+            let requester = connect_requester(kind, restore_args, config)?;
+            let backup = Backup::from_file(restore_args.value_of("file").unwrap())?;
+            backup::restore(&*requester, &backup)?
+        }
+        // Direct I2C access to the sensor, evaluating from the same
+        // list of known commands as the REP server.
+        ("sensor", Some(sensor_args)) => {
+            let named = named_sensor(kind, sensor_args, config)?;
+            let path = resolve(
+                sensor_args.value_of("I2C_PATH"),
+                named.and_then(|s| s.path.as_ref()),
+                "I2C_PATH",
+            )?;
+            let addr = resolve_address(
+                sensor_args.value_of("I2C_ADDRESS"),
+                named.and_then(|s| s.address),
+                "I2C_ADDRESS",
+            )?;
+
+            let device = kind.device(&path, addr)?;
+            let delay_ms = parse_arg(sensor_args, "delay")?
+                .or_else(|| named.and_then(|s| s.delay_ms));
+            let mut pacer = Pacer::new(delay_ms);
+
+            let fail_fast = sensor_args.is_present("fail-fast");
+            let mut output = Output::new(output_format(sensor_args)?, "I2C");
+            let _exec = match sensor_args.values_of("cmds") {
+                Some(cmds) => eval_batch(kind, cmds, &mut output, fail_fast, |s| {
+                    pacer.pace(s, |s| device.eval(s))
+                })?,
+                None => {
+                    let shell = CommanderReadline::Device(kind);
+                    let endpoint = format!("{}-{}", path, addr);
+                    let history = shell_history(args, config, &shell, Some(&endpoint));
+                    shell.start(&history, |s| {
+                        describe_result(pacer.pace(s, |s| device.eval(s)))
+                    })?
+                }
+            };
         }
         _ => println!("{}", args.usage()),
     };
@@ -226,27 +501,106 @@ fn evaluate(args: &ArgMatches) -> Result<()> {
 fn parse_command_line<'c>() -> Result<ArgMatches<'c>> {
     // Setup the app that will parse the command-line.
     let parser_app = BenitaCommanderApp::new();
-    let matches = parser_app.get_matches();
+    let matches = match parser_app.get_matches_safe() {
+        Ok(matches) => matches,
+        // Usage errors, as opposed to `--help` and `--version`.
+        Err(ref e) if e.use_stderr() => {
+            eprintln!("{}", e.message);
+            process::exit(status::EXIT_USAGE);
+        }
+        Err(e) => e.exit(),
+    };
     Ok(matches)
 }
 
+/// History of the shell, from `--history-file`, or in the history directory.
+fn shell_history(
+    args: &ArgMatches,
+    config: &Config,
+    shell: &CommanderReadline,
+    endpoint: Option<&str>,
+) -> History {
+    let settings = config.history.as_ref();
+    let path = match global_value(args, "history-file") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => settings
+            .and_then(|h| h.dir.as_ref())
+            .map(PathBuf::from)
+            .or_else(default_history_dir)
+            .map(|dir| dir.join(shell.history_name(endpoint))),
+    };
+    History {
+        path,
+        max_size: settings
+            .and_then(|h| h.max_size)
+            .unwrap_or(DEFAULT_HISTORY_SIZE),
+    }
+}
+
+/// Settings of the logger, from the configuration file, overridden by the
+/// command-line.
+fn log_settings(args: &ArgMatches, config: &Config) -> Result<LogSettings> {
+    let mut settings = LogSettings::from_config(config.logging.as_ref())?;
+    if let Some(level) = global_value(args, "log-level") {
+        settings.level = parse_level(level)?;
+    }
+    let verbosity = occurrences(args, "verbose") as i64 - occurrences(args, "quiet") as i64;
+    settings.level = adjust_level(settings.level, verbosity);
+    if let Some(dir) = global_value(args, "log-dir") {
+        settings.dir = PathBuf::from(dir);
+    }
+    if occurrences(args, "no-log-file") > 0 {
+        settings.files = false;
+    }
+    if let Some(format) = global_value(args, "log-format") {
+        settings.format = format.parse()?;
+    }
+    if occurrences(args, "syslog") > 0 {
+        settings.syslog = true;
+    }
+    Ok(settings)
+}
+
+/// Value of a global argument, which may be given after any subcommand.
+fn global_value<'c>(args: &'c ArgMatches, name: &str) -> Option<&'c str> {
+    args.value_of(name)
+        .or_else(|| args.subcommand().1.and_then(|m| global_value(m, name)))
+}
+
+/// Occurrences of a global flag, added up over the subcommands.
+fn occurrences(args: &ArgMatches, name: &str) -> u64 {
+    args.occurrences_of(name) + args.subcommand().1.map_or(0, |m| occurrences(m, name))
+}
+
 /// Main program. Starts logger, then evaluates args from stdin.
 fn run_code() -> Result<()> {
-    {
-        // Parse the user input from the command-line.
-        let matched_args = parse_command_line()?;
+    // Parse the user input from the command-line.
+    let matched_args = parse_command_line()?;
 
-        // Initialize logging.
-        //let _log = start_log()?;
+    // Read the configuration file, if any.
+    let config = match global_value(&matched_args, "config") {
+        Some(path) => Config::from_file(path)?,
+        None => Config::default(),
+    };
 
-        info!("benita-command starting");
-        let _run = evaluate(&matched_args)?;
-    }
+    // Initialize logging, as set in the configuration file and the
+    // command-line.
+    let _log = start_log(&log_settings(&matched_args, &config)?)?;
+
+    info!("benita-command starting");
+    let run = evaluate(&matched_args, &config);
 
-    warn!("About to stop calibrated-service");
+    // The log files are flushed and closed, whether the command failed or not.
     info!("benita-command stopping");
-    Ok(())
+    stop_log();
+    run
 }
 
-// fn main() wrapped to handle error chains
-quick_main!(run_code);
+// fn main() wrapped to handle error chains, exiting with a code for each
+// kind of failure.
+fn main() {
+    if let Err(ref e) = run_code() {
+        eprintln!("{}", e.display_chain());
+        process::exit(status::exit_code(e));
+    }
+}
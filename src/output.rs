@@ -0,0 +1,211 @@
+//! Output of the responses to commands, as text, JSON, or CSV.
+use std::str::FromStr;
+
+use chrono;
+use serde_json;
+
+use super::errors::*;
+use super::sensors::{command_name, SensorKind};
+use super::status::Status;
+
+/// Columns of the CSV output.
+pub const CSV_HEADER: &str = "timestamp,kind,command,status,value,units,reply,error";
+
+/// Formats of the output of batch mode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<OutputFormat> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => bail!(ErrorKind::InvalidArgs),
+        }
+    }
+}
+
+/// Outcome of a command sent to a sensor.
+#[derive(Debug, Serialize)]
+pub struct Record {
+    pub timestamp: String,
+    pub kind: &'static str,
+    pub command: String,
+    pub status: &'static str,
+    /// Reply of the sensor, as is.
+    pub reply: Option<String>,
+    /// First number in the reply.
+    pub value: Option<f64>,
+    /// Units of the value, for readings.
+    pub units: Option<&'static str>,
+    pub error: Option<String>,
+}
+
+impl Record {
+    pub fn new(kind: SensorKind, command: &str, result: &Result<String>) -> Record {
+        let (reply, error) = match *result {
+            Ok(ref reply) => (Some(reply.clone()), None),
+            Err(ref e) => (None, Some(e.to_string())),
+        };
+        let value = reply.as_ref().and_then(|r| parse_value(r));
+        let units = match value {
+            Some(_) if command_name(command) == "R" => Some(kind.spec().units),
+            _ => None,
+        };
+        Record {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            kind: kind.name(),
+            command: command.to_string(),
+            status: Status::of(result).name(),
+            reply,
+            value,
+            units,
+            error,
+        }
+    }
+
+    /// Row of the watch mode, aligned with the rows of other sensors.
+    pub fn to_row(&self) -> String {
+        let detail = match (self.value, self.units, &self.reply, &self.error) {
+            (Some(value), Some(units), _, _) => format!("{} {}", value, units),
+            (_, _, &Some(ref reply), _) => reply.clone(),
+            (_, _, _, &Some(ref error)) => error.clone(),
+            _ => String::new(),
+        };
+        format!(
+            "{}  {:<12}  {:<16}  {}",
+            self.timestamp, self.kind, self.status, detail
+        )
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).chain_err(|| "could not write the output as JSON")
+    }
+
+    /// Row of the CSV output, with the columns of `CSV_HEADER`.
+    pub fn to_csv(&self) -> String {
+        let value = self.value.map(|v| v.to_string());
+        let fields: Vec<Option<&str>> = vec![
+            Some(self.timestamp.as_str()),
+            Some(self.kind),
+            Some(self.command.as_str()),
+            Some(self.status),
+            value.as_ref().map(|v| v.as_str()),
+            self.units,
+            self.reply.as_ref().map(|r| r.as_str()),
+            self.error.as_ref().map(|e| e.as_str()),
+        ];
+        fields
+            .into_iter()
+            .map(|field| csv_field(field.unwrap_or("")))
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+/// Prints the outcomes of commands in the given format.
+pub struct Output {
+    format: OutputFormat,
+    /// Prefix of the responses in text output, e.g. `REP`.
+    prefix: &'static str,
+    header_printed: bool,
+}
+
+impl Output {
+    pub fn new(format: OutputFormat, prefix: &'static str) -> Output {
+        Output {
+            format,
+            prefix,
+            header_printed: false,
+        }
+    }
+
+    pub fn print(&mut self, record: &Record) -> Result<()> {
+        match self.format {
+            OutputFormat::Text => match (&record.reply, &record.error) {
+                (&Some(ref reply), _) => println!("{}: {}", self.prefix, reply),
+                (_, &Some(ref error)) => eprintln!("ERROR: {}: {}", record.command, error),
+                _ => {}
+            },
+            OutputFormat::Json => println!("{}", record.to_json()?),
+            OutputFormat::Csv => {
+                if !self.header_printed {
+                    println!("{}", CSV_HEADER);
+                    self.header_printed = true;
+                }
+                println!("{}", record.to_csv());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// First number in the reply, e.g. `7.00` in `7.00` or `1413,707,0.00,1.000`.
+pub fn parse_value(reply: &str) -> Option<f64> {
+    reply
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter_map(|token| token.trim().parse::<f64>().ok())
+        .next()
+}
+
+/// Quotes the field when it has commas, quotes, or line breaks.
+pub fn csv_field(field: &str) -> String {
+    if field.contains(|c: char| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_of_readings_have_values_and_units() {
+        let record = Record::new(SensorKind::Ph, "R", &Ok("7.02".to_string()));
+        assert_eq!(record.status, "ok");
+        assert_eq!(record.value, Some(7.02));
+        assert_eq!(record.units, Some("pH"));
+
+        let record = Record::new(SensorKind::Ph, "L,?", &Ok("?L,1".to_string()));
+        assert_eq!(record.units, None);
+
+        let failed: Result<String> = Err(ErrorKind::UnknownCommand("X".to_string()).into());
+        let record = Record::new(SensorKind::Ph, "X", &failed);
+        assert_eq!(record.status, "unknown-command");
+        assert_eq!(record.reply, None);
+        assert_eq!(record.error, Some("unknown command: X".to_string()));
+    }
+
+    #[test]
+    fn csv_fields_with_commas_are_quoted() {
+        let record = Record::new(
+            SensorKind::Conductivity,
+            "R",
+            &Ok("1413,707,0.00,1.000".to_string()),
+        );
+        let row = record.to_csv();
+        assert!(row.ends_with(",conductivity,R,ok,1413,µS/cm,\"1413,707,0.00,1.000\","));
+    }
+
+    #[test]
+    fn watch_rows_show_readings_with_units() {
+        let record = Record::new(SensorKind::Ph, "R", &Ok("7.02".to_string()));
+        assert!(record.to_row().ends_with("  ph            ok                7.02 pH"));
+    }
+
+    #[test]
+    fn output_formats_parse_from_their_names() {
+        assert_eq!("json".parse::<OutputFormat>().unwrap(), OutputFormat::Json);
+        assert_eq!("csv".parse::<OutputFormat>().unwrap(), OutputFormat::Csv);
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+}
@@ -0,0 +1,94 @@
+//! Pacing of the commands sent to a sensor. `benita` already waits for the
+//! processing time of each command before reading its reply over I2C, so
+//! commands are sent back to back, unless a pause after every command is
+//! asked for, e.g. with `--delay`.
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::errors::*;
+use super::status::Status;
+
+/// Keeps track of when a sensor is ready for its next command.
+pub struct Pacer {
+    /// Pause after every command, if any.
+    delay: Option<Duration>,
+    ready_at: Option<Instant>,
+}
+
+impl Pacer {
+    pub fn new(delay_ms: Option<u64>) -> Pacer {
+        Pacer {
+            delay: delay_ms.map(Duration::from_millis),
+            ready_at: None,
+        }
+    }
+
+    /// Evaluates the command once the pause after the previous one is over.
+    /// Commands that the sensor doesn't know never reach it, and need no
+    /// pause.
+    pub fn pace<T, F>(&mut self, cmd: &str, eval: F) -> Result<T>
+    where
+        F: FnOnce(&str) -> Result<T>,
+    {
+        self.wait();
+        let result = eval(cmd);
+        if Status::of(&result) != Status::UnknownCommand {
+            self.finished();
+        }
+        result
+    }
+
+    /// Starts the pause after the command that the sensor just replied to.
+    pub fn finished(&mut self) {
+        self.ready_at = self.delay.map(|delay| Instant::now() + delay);
+    }
+
+    /// Waits until the pause after the last command is over.
+    pub fn wait(&self) {
+        if let Some(ready_at) = self.ready_at {
+            let now = Instant::now();
+            if ready_at > now {
+                thread::sleep(ready_at - now);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(_: &str) -> Result<()> {
+        Ok(())
+    }
+
+    #[test]
+    fn commands_are_sent_back_to_back_without_a_delay() {
+        let mut pacer = Pacer::new(None);
+        let _first = pacer.pace("R", reply);
+        let start = Instant::now();
+        let _next = pacer.pace("R", reply);
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn pauses_start_when_the_command_is_done() {
+        let mut pacer = Pacer::new(Some(100));
+        let slow = |_: &str| -> Result<()> {
+            thread::sleep(Duration::from_millis(100));
+            Ok(())
+        };
+        let _slow = pacer.pace("R", slow);
+        let start = Instant::now();
+        let _next = pacer.pace("R", reply);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        let mut pacer = Pacer::new(Some(100));
+        let _unknown = pacer.pace("Zzz", |s| -> Result<()> {
+            bail!(ErrorKind::UnknownCommand(s.to_string()))
+        });
+        let start = Instant::now();
+        let _next = pacer.pace("R", reply);
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+}
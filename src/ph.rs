@@ -6,73 +6,45 @@ mod errors {
 pub use self::errors::*;
 
 use benita::ezo::devices::{I2CCommand, I2CResponse};
-use benita::ezo::network::{Endpoint, SocketReply, SocketRequest};
+use benita::ezo::network::SocketRequest;
 use benita::ezo::ph::device::PhSensor;
-use benita::ezo::ph::network::{PhRequester, PhResponder};
 use benita::ezo::ph::network::requests::*;
-use benita::utilities::{create_and_bind_responder, create_and_connect_requester};
+use sensors::{SensorKind, SensorSpec};
 
-// Define PhREQ type
-requester! {
-    PhREQ,
-     "A request (REQ) client for pH servers.",
-     PhRequester,
-     PhRequester,
-     [
-         CalibrationClear,
-         CalibrationHigh,
-         CalibrationLow,
-         CalibrationMid,
-         CalibrationState,
-         CompensationGet,
-         CompensationSet,
-         DeviceInformation,
-         Export,
-         ExportInfo,
-         Import,
-         Find,
-         LedOff,
-         LedOn,
-         LedState,
-         ProtocolLockDisable,
-         ProtocolLockEnable,
-         ProtocolLockState,
-         Reading,
-         Status,
-         Sleep,
-         Slope
-             ]
-}
-
-// Creates a responder with an explicit list of commands that it handles.
-responder! {
-    PhREP,
-     "A response (REP) server for a pH device which handles a list of commands.",
-     PhSensor,
-     PhResponder,
-     PhResponder,
-     [
-         CalibrationClear,
-         CalibrationHigh,
-         CalibrationLow,
-         CalibrationMid,
-         CalibrationState,
-         CompensationGet,
-         CompensationSet,
-         DeviceInformation,
-         Export,
-         ExportInfo,
-         Import,
-         Find,
-         LedOff,
-         LedOn,
-         LedState,
-         ProtocolLockDisable,
-         ProtocolLockEnable,
-         ProtocolLockState,
-         Reading,
-         Status,
-         Sleep,
-         Slope
-     ]
+// Describes pH sensors, and creates their REQ client, REP server, and
+// I2C device handler, which handle the requests below.
+sensor! {
+    PhSensor,
+    SensorKind::Ph,
+    "ph",
+    "Commands for pH",
+    "PH",
+    "pH",
+    PhREQ: "A request (REQ) client for pH servers.",
+    PhREP: "A response (REP) server for a pH device which handles a list of commands.",
+    PhDevice: "A direct I2C handler for a pH device which handles a list of commands.",
+    [
+        CalibrationClear => "Cal,clear",
+        CalibrationHigh => "Cal,high,<pH>",
+        CalibrationLow => "Cal,low,<pH>",
+        CalibrationMid => "Cal,mid,<pH>",
+        CalibrationState => "Cal,?",
+        CompensationGet => "T,?",
+        CompensationSet => "T,<°C>",
+        DeviceInformation => "I",
+        Export => "Export",
+        ExportInfo => "Export,?",
+        Import => "Import,<calibration>",
+        Find => "Find",
+        LedOff => "L,0",
+        LedOn => "L,1",
+        LedState => "L,?",
+        ProtocolLockDisable => "Plock,0",
+        ProtocolLockEnable => "Plock,1",
+        ProtocolLockState => "Plock,?",
+        Reading => "R",
+        Status => "Status",
+        Sleep => "Sleep",
+        Slope => "Slope,?"
+    ]
 }
@@ -1,6 +1,14 @@
 //! Command-line readers for user interaction.
+use std::fs;
+use std::path::{Path, PathBuf};
+
 use rustyline::error::ReadlineError;
-use rustyline::Editor;
+use rustyline::{CompletionType, Config, Editor};
+use xdg;
+
+use super::completion::CommandCompleter;
+use super::console;
+use super::sensors::SensorKind;
 
 mod errors {
     //! Errors and their descriptions
@@ -20,46 +28,162 @@ mod errors {
 
 pub use self::errors::*;
 
-pub enum SensorKind {
-    Conductivity,
-    Ph,
-    Temperature,
+/// Lines kept in a history file, unless set otherwise.
+pub const DEFAULT_HISTORY_SIZE: usize = 1000;
+
+/// History of a shell.
+#[derive(Clone, Debug, PartialEq)]
+pub struct History {
+    /// File that the history is kept in, or `None` to keep it for the
+    /// session only.
+    pub path: Option<PathBuf>,
+    /// Lines kept, the oldest ones are dropped first.
+    pub max_size: usize,
+}
+
+/// Directory of the history files, under the XDG data directory, e.g.
+/// `~/.local/share/benita-commander/history`.
+pub fn default_history_dir() -> Option<PathBuf> {
+    xdg::BaseDirectories::with_prefix("benita-commander")
+        .ok()
+        .map(|dirs| dirs.get_data_home().join("history"))
 }
 
 /// Interactive command line-reader for `benita-commander`
 pub enum CommanderReadline {
+    /// Top-level shell.
     Main,
-    MainSensor(SensorKind),
+    /// Shell that writes commands directly to a sensor over I2C.
     Device(SensorKind),
+    /// Shell that sends commands to a sensor's REP server.
     Socket(SensorKind),
 }
 
 impl CommanderReadline {
-    pub fn eval<F>(&self, line: &str, callback: &F) -> Result<String>
+    /// The prompt shown for every line.
+    pub fn prompt(&self) -> String {
+        match *self {
+            CommanderReadline::Main => ">> ".to_string(),
+            CommanderReadline::Socket(ref kind) => format!("{}>> ", kind.name()),
+            CommanderReadline::Device(ref kind) => format!("{}-sensor>> ", kind.name()),
+        }
+    }
+
+    /// Commands understood by the shell.
+    pub fn commands(&self) -> &'static [&'static str] {
+        match *self {
+            CommanderReadline::Main => console::COMMANDS,
+            CommanderReadline::Device(ref kind) | CommanderReadline::Socket(ref kind) => {
+                kind.commands()
+            }
+        }
+    }
+
+    /// Name of the history file of the shell, for the sensor at `endpoint`,
+    /// e.g. `ph-req-tcp___127_0_0_1_7778.history`.
+    pub fn history_name(&self, endpoint: Option<&str>) -> String {
+        let name = match *self {
+            CommanderReadline::Main => "console".to_string(),
+            CommanderReadline::Device(ref kind) => format!("{}-sensor", kind.name()),
+            CommanderReadline::Socket(ref kind) => format!("{}-req", kind.name()),
+        };
+        match endpoint {
+            Some(endpoint) => {
+                let endpoint: String = endpoint
+                    .chars()
+                    .map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' })
+                    .collect();
+                format!("{}-{}.history", name, endpoint)
+            }
+            None => format!("{}.history", name),
+        }
+    }
+
+    /// Help text describing the shell, and the commands it understands.
+    pub fn help(&self) -> String {
+        let about = match *self {
+            CommanderReadline::Main => {
+                "Commands are sent to the sensor in use, after connecting to it.".to_string()
+            }
+            CommanderReadline::Device(ref kind) => format!(
+                "Commands are written directly to the {} sensor over I2C.",
+                kind.name()
+            ),
+            CommanderReadline::Socket(ref kind) => format!(
+                "Commands are sent to the {} REP server over the network.",
+                kind.name()
+            ),
+        };
+        let mut help = format!("{}\n", about);
+        if !self.commands().is_empty() {
+            help.push_str("\nAvailable commands:\n");
+            for cmd in self.commands() {
+                help.push_str(&format!("    {}\n", cmd));
+            }
+        }
+        help.push_str("\nPress TAB to complete a command, and type `help <command>` to show its");
+        help.push_str("\narguments. Type `help` to show this message, `q` or `quit` to exit.");
+        help
+    }
+
+    /// Syntax of the commands that start like `prefix`, e.g. `Cal,mid,<pH>`
+    /// for `Cal,mid`.
+    pub fn hint(&self, prefix: &str) -> String {
+        let hints = CommandCompleter::new(self.commands()).hints(prefix);
+        if hints.is_empty() {
+            format!("No command starts with `{}`.", prefix)
+        } else {
+            hints.join("\n")
+        }
+    }
+
+    pub fn eval<F>(&self, line: &str, callback: &mut F) -> Result<String>
     where
-        F: Fn(&str) -> String,
+        F: FnMut(&str) -> String,
     {
         let result = callback(&line);
         Ok(result)
     }
 
-    pub fn start<F>(&self, prompt: &str, callback: F) -> Result<()>
+    /// Reads lines until the shell is quit, keeping them in the history.
+    /// Failures to load or save the history are only warnings.
+    pub fn start<F>(&self, history: &History, mut callback: F) -> Result<()>
     where
-        F: Fn(&str) -> String,
+        F: FnMut(&str) -> String,
     {
-        let mut rl = Editor::<()>::new();
-        if let Err(_) = rl.load_history("history.txt") {
-            println!("No previous history.");
+        let prompt = self.prompt();
+        let config = Config::builder()
+            .max_history_size(history.max_size)
+            .completion_type(CompletionType::List)
+            .build();
+        let mut rl = Editor::<CommandCompleter>::with_config(config);
+        rl.set_completer(Some(CommandCompleter::new(self.commands())));
+        match history.path {
+            Some(ref path) if path.exists() => {
+                if let Err(e) = rl.load_history(path) {
+                    warn!("could not load the history from {}: {}", path.display(), e);
+                }
+            }
+            Some(ref path) => debug!("no previous history in {}", path.display()),
+            None => {}
         }
         loop {
-            let readline = rl.readline(prompt);
+            let readline = rl.readline(&prompt);
             match readline {
                 Ok(line) => {
                     rl.add_history_entry(line.as_ref());
                     if line == "q" || line == "Q" || line == "exit" || line == "quit" {
                         break;
                     }
-                    println!("[.] {}", self.eval(&line, &callback)?);
+                    if line == "help" {
+                        println!("{}", self.help());
+                        continue;
+                    }
+                    if line.starts_with("help ") {
+                        println!("{}", self.hint(line["help ".len()..].trim()));
+                        continue;
+                    }
+                    println!("[.] {}", self.eval(&line, &mut callback)?);
                 }
                 Err(ReadlineError::Interrupted) => {
                     println!("CTRL-C");
@@ -75,7 +199,39 @@ impl CommanderReadline {
                 }
             }
         }
-        rl.save_history("history.txt").unwrap();
+        if let Some(ref path) = history.path {
+            if let Err(e) = save_history(&rl, path) {
+                warn!("could not save the history to {}: {}", path.display(), e);
+            }
+        }
         Ok(())
     }
 }
+
+// Saves the history, creating its directory if missing.
+fn save_history(rl: &Editor<CommandCompleter>, path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        let _dir = fs::create_dir_all(dir)
+            .chain_err(|| format!("could not create the directory: {}", dir.display()))?;
+    }
+    rl.save_history(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_files_are_per_sensor_and_endpoint() {
+        assert_eq!(CommanderReadline::Main.history_name(None), "console.history");
+        assert_eq!(
+            CommanderReadline::Socket(SensorKind::Ph).history_name(Some("tcp://127.0.0.1:7778")),
+            "ph-req-tcp___127_0_0_1_7778.history"
+        );
+        assert_eq!(
+            CommanderReadline::Device(SensorKind::Conductivity).history_name(Some("/dev/i2c-1")),
+            "conductivity-sensor-_dev_i2c-1.history"
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! Recording of sensor readings to CSV files, or to a SQLite database.
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use chrono;
+#[cfg(feature = "sqlite")]
+use rusqlite;
+
+use super::client::ClientConfig;
+use super::config::{resolve, resolve_address, SensorConfig};
+use super::errors::*;
+use super::output::{csv_field, Record};
+use super::sensors::{SensorDevice, SensorKind, SensorRequester};
+use super::server::Shutdown;
+use super::watch::{next_round, wait_until};
+
+/// Columns of the CSV files.
+pub const CSV_HEADER: &str = "name,timestamp,kind,command,status,value,units,reply,error";
+
+/// How a recorded sensor is reached.
+pub enum Source {
+    Requester(Box<SensorRequester>),
+    Device(Box<SensorDevice>),
+}
+
+impl Source {
+    pub fn eval(&self, s: &str) -> Result<String> {
+        match *self {
+            Source::Requester(ref requester) => requester.eval(s),
+            Source::Device(ref device) => device.eval(s),
+        }
+    }
+}
+
+/// A named sensor, from the configuration file.
+pub struct RecordedSensor {
+    pub name: String,
+    pub kind: SensorKind,
+    pub source: Source,
+}
+
+impl RecordedSensor {
+    /// Connects to the REP server of the sensor, or accesses it over I2C if
+    /// `direct` is set, or if it has no `connect` URL.
+    pub fn new(
+        name: &str,
+        sensor: &SensorConfig,
+        direct: bool,
+        client: &ClientConfig,
+    ) -> Result<RecordedSensor> {
+        let kind = sensor.kind()?;
+        let source = match sensor.connect {
+            Some(ref url) if !direct => Source::Requester(kind.requester(url, client)?),
+            _ => {
+                let path = resolve(None, sensor.path.as_ref(), &format!("{}.path", name))?;
+                let addr = resolve_address(None, sensor.address, &format!("{}.address", name))?;
+                Source::Device(kind.device(&path, addr)?)
+            }
+        };
+        Ok(RecordedSensor {
+            name: name.to_string(),
+            kind,
+            source,
+        })
+    }
+}
+
+/// Storage of the readings.
+pub trait Sink {
+    fn append(&mut self, name: &str, record: &Record) -> Result<()>;
+}
+
+/// CSV files in a directory, one per day, e.g. `readings-2017-11-20.csv`.
+pub struct CsvSink {
+    dir: PathBuf,
+    current: Option<(String, File)>,
+}
+
+impl CsvSink {
+    pub fn new(dir: &str) -> Result<CsvSink> {
+        let _dir = fs::create_dir_all(dir)
+            .chain_err(|| format!("could not create the directory: {}", dir))?;
+        Ok(CsvSink {
+            dir: PathBuf::from(dir),
+            current: None,
+        })
+    }
+
+    // The file of the day, opened anew when the day changes.
+    fn file(&mut self) -> Result<&mut File> {
+        let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let rotate = match self.current {
+            Some((ref current, _)) => *current != date,
+            None => true,
+        };
+        if rotate {
+            let path = self.dir.join(format!("readings-{}.csv", date));
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .chain_err(|| format!("could not open {}", path.display()))?;
+            let is_new = file.metadata().map(|m| m.len() == 0).unwrap_or(false);
+            if is_new {
+                writeln!(file, "{}", CSV_HEADER).chain_err(|| "could not write the CSV header")?;
+            }
+            self.current = Some((date, file));
+        }
+        Ok(&mut self.current.as_mut().expect("file was just opened").1)
+    }
+}
+
+impl Sink for CsvSink {
+    fn append(&mut self, name: &str, record: &Record) -> Result<()> {
+        let row = format!("{},{}", csv_field(name), record.to_csv());
+        let file = self.file()?;
+        writeln!(file, "{}", row).chain_err(|| "could not write the reading")
+    }
+}
+
+/// Table `readings` of a SQLite database.
+#[cfg(feature = "sqlite")]
+pub struct SqliteSink {
+    conn: rusqlite::Connection,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteSink {
+    pub fn new(path: &str) -> Result<SqliteSink> {
+        let conn = rusqlite::Connection::open(path)
+            .chain_err(|| format!("could not open the database: {}", path))?;
+        let _table = conn.execute(
+            "CREATE TABLE IF NOT EXISTS readings (
+                name TEXT NOT NULL,
+                timestamp TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                command TEXT NOT NULL,
+                status TEXT NOT NULL,
+                value REAL,
+                units TEXT,
+                reply TEXT,
+                error TEXT
+            )",
+            &[],
+        ).chain_err(|| "could not create the readings table")?;
+        Ok(SqliteSink { conn })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl Sink for SqliteSink {
+    fn append(&mut self, name: &str, record: &Record) -> Result<()> {
+        let _row = self.conn
+            .execute(
+                "INSERT INTO readings
+                 (name, timestamp, kind, command, status, value, units, reply, error)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                &[
+                    &name,
+                    &record.timestamp,
+                    &record.kind,
+                    &record.command,
+                    &record.status,
+                    &record.value,
+                    &record.units,
+                    &record.reply,
+                    &record.error,
+                ],
+            )
+            .chain_err(|| "could not insert the reading")?;
+        Ok(())
+    }
+}
+
+/// Takes a reading of every sensor, once per interval, and appends them to
+/// the sink. Stops after `count` rounds, or when the shutdown is triggered.
+pub fn record(
+    sensors: &[RecordedSensor],
+    sink: &mut Sink,
+    interval: Duration,
+    count: Option<u64>,
+    shutdown: &Shutdown,
+) -> Result<()> {
+    let mut start = Instant::now();
+    let mut round = 0;
+    while !shutdown.is_triggered() && count.map_or(true, |count| round < count) {
+        for sensor in sensors {
+            let result = sensor.source.eval("R");
+            if let Err(ref e) = result {
+                warn!("{} could not be read: {}", sensor.name, e);
+            }
+            sink.append(&sensor.name, &Record::new(sensor.kind, "R", &result))?;
+        }
+        round += 1;
+        start = next_round(start, interval, Instant::now());
+        wait_until(start, shutdown);
+    }
+    Ok(())
+}
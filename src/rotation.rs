@@ -0,0 +1,283 @@
+//! Rotation of log files, by size and by day, keeping a number of old files,
+//! which may be compressed with gzip.
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use chrono;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+use super::logging::{ErrorKind, Result, ResultExt};
+
+/// When to rotate a log file, and how many old files to keep.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rotation {
+    /// Size in bytes, or no limit if `None`.
+    pub max_size: Option<u64>,
+    /// Rotate when the day changes.
+    pub daily: bool,
+    /// Old files kept, e.g. `benita-commander.log.1` to `.7`.
+    pub keep: usize,
+    /// Compress the old files with gzip.
+    pub compress: bool,
+}
+
+impl Default for Rotation {
+    fn default() -> Rotation {
+        Rotation {
+            max_size: Some(10 * 1024 * 1024),
+            daily: true,
+            keep: 7,
+            compress: false,
+        }
+    }
+}
+
+/// A log file that is rotated: `benita-commander.log` is renamed to
+/// `benita-commander.log.1`, which is renamed to `.2`, and so on, up to the
+/// number of old files kept.
+///
+/// Several processes may share the file, e.g. the console and a REP server,
+/// so the file is opened again when another one has rotated it.
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: Rotation,
+    file: File,
+    size: u64,
+    /// Day of the lines in the file.
+    date: String,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, rotation: Rotation) -> Result<RotatingFile> {
+        let mut file = RotatingFile {
+            path: path.to_path_buf(),
+            rotation,
+            file: open_append(path)?,
+            size: 0,
+            date: today(),
+        };
+        file.update();
+        Ok(file)
+    }
+
+    /// Append the line, rotating the file first if it is due.
+    pub fn write_line(&mut self, line: &str) -> Result<()> {
+        if self.was_moved() {
+            self.file = open_append(&self.path)?;
+        }
+        // Other processes may have written to the file as well.
+        self.update();
+        let len = line.len() as u64 + 1;
+        if self.is_due(len) {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line).chain_err(|| self.failed())?;
+        self.size += len;
+        Ok(())
+    }
+
+    // Takes the size, and the day of the lines, from the open file. A file
+    // left from another day is rotated before the first line.
+    fn update(&mut self) {
+        let metadata = match self.file.metadata() {
+            Ok(metadata) => metadata,
+            Err(_) => return,
+        };
+        self.size = metadata.len();
+        self.date = match metadata.modified() {
+            Ok(modified) if self.size > 0 => day(chrono::DateTime::from(modified)),
+            _ => today(),
+        };
+    }
+
+    // Whether the path no longer leads to the open file, because another
+    // process rotated or removed it.
+    fn was_moved(&self) -> bool {
+        match (fs::metadata(&self.path), self.file.metadata()) {
+            (Ok(current), Ok(open)) => current.dev() != open.dev() || current.ino() != open.ino(),
+            (Err(_), _) => true,
+            (_, Err(_)) => false,
+        }
+    }
+
+    // Whether the line doesn't fit in the file, or the day changed. Lines
+    // larger than the limit still go to an empty file.
+    fn is_due(&self, len: u64) -> bool {
+        let too_big = self.rotation
+            .max_size
+            .map_or(false, |max| self.size > 0 && self.size + len > max);
+        too_big || (self.rotation.daily && self.date != today())
+    }
+
+    /// Move the file to the first old file, then continue with an empty one.
+    pub fn rotate(&mut self) -> Result<()> {
+        let _flush = self.file.flush();
+        let keep = self.rotation.keep;
+        // The oldest file is removed, the rest move up by one.
+        for n in (1..keep + 1).rev() {
+            for &compressed in &[false, true] {
+                let from = old_file(&self.path, n, compressed);
+                if !from.exists() {
+                    continue;
+                }
+                let _moved = if n == keep {
+                    fs::remove_file(&from)
+                } else {
+                    fs::rename(&from, old_file(&self.path, n + 1, compressed))
+                }.chain_err(|| self.failed())?;
+            }
+        }
+        if keep == 0 {
+            let _removed = fs::remove_file(&self.path).chain_err(|| self.failed())?;
+        } else {
+            let first = old_file(&self.path, 1, false);
+            let _moved = fs::rename(&self.path, &first).chain_err(|| self.failed())?;
+            if self.rotation.compress {
+                let _compressed = gzip(&first).chain_err(|| self.failed())?;
+            }
+        }
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        self.date = today();
+        Ok(())
+    }
+
+    fn failed(&self) -> ErrorKind {
+        ErrorKind::LogFileFailed(self.path.display().to_string())
+    }
+}
+
+/// Path of an old log file, e.g. `benita-commander.log.2.gz`.
+pub fn old_file(path: &Path, n: usize, compressed: bool) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    if compressed {
+        name.push(".gz");
+    }
+    PathBuf::from(name)
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .chain_err(|| ErrorKind::LogFileNotFound(path.display().to_string()))
+}
+
+// Compresses the file to a `.gz` file next to it, then removes it.
+fn gzip(path: &Path) -> io::Result<()> {
+    let mut gz_path = path.as_os_str().to_owned();
+    gz_path.push(".gz");
+    let mut input = File::open(path)?;
+    let mut encoder = GzEncoder::new(File::create(&gz_path)?, Compression::Default);
+    let _copied = io::copy(&mut input, &mut encoder)?;
+    let _file = encoder.finish()?;
+    fs::remove_file(path)
+}
+
+fn today() -> String {
+    day(chrono::Local::now())
+}
+
+fn day(time: chrono::DateTime<chrono::Local>) -> String {
+    time.format("%Y-%m-%d").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::ffi::CString;
+    use std::io::Read;
+    use std::os::unix::ffi::OsStrExt;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use chrono::TimeZone;
+    use libc;
+
+    // A directory of its own for each test, since tests run concurrently.
+    fn test_dir(test: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
+        let dir = env::temp_dir().join(format!("benita-commander-{}-{}", test, nanos));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn contents(path: &Path) -> String {
+        let mut contents = String::new();
+        let _read = File::open(path).unwrap().read_to_string(&mut contents);
+        contents
+    }
+
+    // Sets the access and modification times of the file.
+    fn set_modified(path: &Path, time: chrono::DateTime<chrono::Local>) {
+        let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+        let times = libc::utimbuf {
+            actime: time.timestamp() as libc::time_t,
+            modtime: time.timestamp() as libc::time_t,
+        };
+        assert_eq!(unsafe { libc::utime(path.as_ptr(), &times) }, 0);
+    }
+
+    #[test]
+    fn files_rotate_by_size_keeping_old_files() {
+        let dir = test_dir("rotation-by-size");
+        let path = dir.join("benita-commander.log");
+        let rotation = Rotation {
+            max_size: Some(10),
+            daily: false,
+            keep: 2,
+            compress: true,
+        };
+        let mut file = RotatingFile::open(&path, rotation).unwrap();
+        for line in &["first", "second", "third", "fourth"] {
+            file.write_line(line).unwrap();
+        }
+        assert_eq!(contents(&path), "fourth\n");
+        assert!(old_file(&path, 1, true).exists());
+        assert!(old_file(&path, 2, true).exists());
+        assert!(!old_file(&path, 1, false).exists());
+        assert!(!old_file(&path, 3, true).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn files_from_another_day_rotate_on_the_first_line() {
+        let dir = test_dir("rotation-by-day");
+        let path = dir.join("benita-commander.log");
+        let _written = File::create(&path).unwrap().write_all(b"yesterday\n");
+        set_modified(&path, chrono::Local.ymd(2017, 11, 20).and_hms(12, 0, 0));
+        let mut file = RotatingFile::open(&path, Rotation::default()).unwrap();
+        file.write_line("today").unwrap();
+        assert_eq!(contents(&path), "today\n");
+        assert_eq!(contents(&old_file(&path, 1, false)), "yesterday\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn files_rotated_by_another_process_are_opened_again() {
+        let dir = test_dir("rotation-by-another-process");
+        let path = dir.join("benita-commander.log");
+        let rotation = Rotation {
+            max_size: None,
+            daily: false,
+            keep: 1,
+            compress: false,
+        };
+        let mut first = RotatingFile::open(&path, rotation).unwrap();
+        let mut second = RotatingFile::open(&path, rotation).unwrap();
+        first.write_line("first").unwrap();
+        first.rotate().unwrap();
+        second.write_line("second").unwrap();
+        assert_eq!(contents(&path), "second\n");
+        assert_eq!(contents(&old_file(&path, 1, false)), "first\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
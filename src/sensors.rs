@@ -0,0 +1,211 @@
+//! Generic interface shared by every sensor module.
+//!
+//! Each sensor module describes itself with a `SensorSpec`, and its REQ, REP
+//! and Device types implement `SensorRequester`, `SensorResponder`, and
+//! `SensorDevice`, respectively. Adding a new sensor only requires a new
+//! module, and a new `SensorKind` variant that points to it.
+use std::str::FromStr;
+
+use super::client::ClientConfig;
+use super::errors::*;
+use super::conductivity::{self, ConductivityDevice, ConductivityREP, ConductivityREQ};
+use super::ph::{self, PhDevice, PhREP, PhREQ};
+use super::temperature::{self, TemperatureDevice, TemperatureREP, TemperatureREQ};
+
+/// Static description of a sensor.
+pub struct SensorSpec {
+    pub kind: SensorKind,
+    /// Name of the sensor, as used in subcommands and prompts.
+    pub name: &'static str,
+    /// Description of the sensor subcommand.
+    pub about: &'static str,
+    /// Units of the readings of the sensor.
+    pub units: &'static str,
+    /// Prefix of the environment variables read by the sensor subcommands.
+    pub env_prefix: &'static str,
+    pub req_url_env: &'static str,
+    pub rep_url_env: &'static str,
+    pub rep_path_env: &'static str,
+    pub rep_address_env: &'static str,
+    /// Command syntax understood by the sensor.
+    pub commands: &'static [&'static str],
+}
+
+impl SensorSpec {
+    /// Error for a command that the sensor doesn't recognize. If the name of
+    /// the command is known, the error shows its expected syntax.
+    pub fn unrecognized(&self, cmd: &str) -> ErrorKind {
+        let name = command_name(cmd);
+        let syntax = self.commands
+            .iter()
+            .filter(|c| command_name(c) == name)
+            .cloned()
+            .collect::<Vec<&str>>();
+        if name.is_empty() || syntax.is_empty() {
+            ErrorKind::UnknownCommand(cmd.to_string())
+        } else {
+            ErrorKind::InvalidCommand(cmd.to_string(), syntax.join(" | "))
+        }
+    }
+}
+
+/// Name of a command, in upper case, e.g. `CAL` for `Cal,mid,7.00`.
+pub fn command_name(cmd: &str) -> String {
+    cmd.split(',').next().unwrap_or("").trim().to_uppercase()
+}
+
+/// A request (REQ) client for a sensor's REP server.
+pub trait SensorRequester {
+    fn spec(&self) -> &'static SensorSpec;
+    /// URL of the REP server.
+    fn url(&self) -> &str;
+    fn eval(&self, s: &str) -> Result<String>;
+}
+
+/// A response (REP) server for a sensor.
+pub trait SensorResponder {
+    fn spec(&self) -> &'static SensorSpec;
+    /// Receives the next request, or `None` if none arrived in time.
+    fn recv(&self) -> Result<Option<String>>;
+    fn send(&self, reply: &str) -> Result<()>;
+    fn eval(&self, s: &str) -> Result<String>;
+}
+
+/// A direct I2C handler for a sensor.
+pub trait SensorDevice {
+    fn spec(&self) -> &'static SensorSpec;
+    fn eval(&self, s: &str) -> Result<String>;
+}
+
+/// The kinds of sensors that `benita-commander` knows how to handle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SensorKind {
+    Conductivity,
+    Ph,
+    Temperature,
+}
+
+impl SensorKind {
+    /// Every known kind of sensor.
+    pub fn all() -> &'static [SensorKind] {
+        static ALL: [SensorKind; 3] = [
+            SensorKind::Conductivity,
+            SensorKind::Ph,
+            SensorKind::Temperature,
+        ];
+        &ALL
+    }
+
+    pub fn spec(&self) -> &'static SensorSpec {
+        match *self {
+            SensorKind::Conductivity => &conductivity::SPEC,
+            SensorKind::Ph => &ph::SPEC,
+            SensorKind::Temperature => &temperature::SPEC,
+        }
+    }
+
+    /// Name of the sensor, as used in subcommands and prompts.
+    pub fn name(&self) -> &'static str {
+        self.spec().name
+    }
+
+    /// Command syntax understood by the sensor.
+    pub fn commands(&self) -> &'static [&'static str] {
+        self.spec().commands
+    }
+
+    /// Whether the readings of the sensor are compensated for the
+    /// temperature set with `T,<°C>`.
+    pub fn temperature_compensated(&self) -> bool {
+        self.commands().contains(&"T,<°C>")
+    }
+
+    /// Creates a REQ client connected to `url`.
+    pub fn requester(&self, url: &str, config: &ClientConfig) -> Result<Box<SensorRequester>> {
+        let requester: Box<SensorRequester> = match *self {
+            SensorKind::Conductivity => Box::new(ConductivityREQ::with_config(url, config)?),
+            SensorKind::Ph => Box::new(PhREQ::with_config(url, config)?),
+            SensorKind::Temperature => Box::new(TemperatureREQ::with_config(url, config)?),
+        };
+        Ok(requester)
+    }
+
+    /// Creates a REP server bound to `url`, for the sensor at `path` and `addr`.
+    pub fn responder(&self, url: &str, path: &str, addr: u16) -> Result<Box<SensorResponder>> {
+        let responder: Box<SensorResponder> = match *self {
+            SensorKind::Conductivity => Box::new(ConductivityREP::new(url, path, addr)?),
+            SensorKind::Ph => Box::new(PhREP::new(url, path, addr)?),
+            SensorKind::Temperature => Box::new(TemperatureREP::new(url, path, addr)?),
+        };
+        Ok(responder)
+    }
+
+    /// Creates a direct I2C handler for the sensor at `path` and `addr`.
+    pub fn device(&self, path: &str, addr: u16) -> Result<Box<SensorDevice>> {
+        let device: Box<SensorDevice> = match *self {
+            SensorKind::Conductivity => Box::new(ConductivityDevice::new(path, addr)?),
+            SensorKind::Ph => Box::new(PhDevice::new(path, addr)?),
+            SensorKind::Temperature => Box::new(TemperatureDevice::new(path, addr)?),
+        };
+        Ok(device)
+    }
+}
+
+impl FromStr for SensorKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<SensorKind> {
+        SensorKind::all()
+            .iter()
+            .find(|kind| kind.name() == s)
+            .cloned()
+            .ok_or_else(|| ErrorKind::UnknownSensor(s.to_string()).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sensor_kinds_parse_from_their_names() {
+        for kind in SensorKind::all() {
+            assert_eq!(kind.name().parse::<SensorKind>().unwrap(), *kind);
+            assert_eq!(kind.spec().kind, *kind);
+        }
+        assert!("orange".parse::<SensorKind>().is_err());
+    }
+
+    #[test]
+    fn sensor_specs_derive_env_vars_from_prefix() {
+        let spec = SensorKind::Ph.spec();
+        assert_eq!(spec.env_prefix, "PH");
+        assert_eq!(spec.req_url_env, "PH_REQ_URL");
+        assert_eq!(spec.rep_url_env, "PH_REP_URL");
+        assert_eq!(spec.rep_path_env, "PH_REP_PATH");
+        assert_eq!(spec.rep_address_env, "PH_REP_ADDRESS");
+    }
+
+    #[test]
+    fn ph_and_conductivity_are_temperature_compensated() {
+        assert!(SensorKind::Ph.temperature_compensated());
+        assert!(SensorKind::Conductivity.temperature_compensated());
+        assert!(!SensorKind::Temperature.temperature_compensated());
+    }
+
+    #[test]
+    fn unrecognized_commands_show_the_expected_syntax() {
+        let spec = SensorKind::Temperature.spec();
+        match spec.unrecognized("Orange") {
+            ErrorKind::UnknownCommand(cmd) => assert_eq!(cmd, "Orange"),
+            kind => panic!("unexpected error: {}", kind),
+        }
+        match spec.unrecognized("Cal,x") {
+            ErrorKind::InvalidCommand(cmd, syntax) => {
+                assert_eq!(cmd, "Cal,x");
+                assert!(syntax.contains("Cal,<°C>"));
+            }
+            kind => panic!("unexpected error: {}", kind),
+        }
+    }
+}
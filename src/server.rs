@@ -0,0 +1,369 @@
+//! REP servers for sensors, and a supervisor that runs several of them.
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Instant;
+
+use ctrlc;
+use zmq;
+
+use super::errors::*;
+use super::logging::{log_exchange, set_sensor, Exchange};
+use super::pacing::Pacer;
+use super::sensors::{SensorKind, SensorResponder};
+use super::status::Status;
+
+/// Prefix of the replies to requests that failed.
+pub const ERROR_REPLY_PREFIX: &str = "error: ";
+
+/// How long a REP server waits for a request before checking whether it
+/// should stop, in milliseconds.
+pub const POLL_INTERVAL_MS: i32 = 500;
+
+/// Location of a sensor, and the URL that its REP server binds to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerConfig {
+    pub kind: SensorKind,
+    pub path: String,
+    pub address: u16,
+    pub url: String,
+    /// Put the sensor to sleep when the server stops.
+    pub sleep_on_exit: bool,
+    /// Consecutive requests failed on the sensor after which the server
+    /// stops. Without it, failed requests never stop the server.
+    pub max_errors: Option<u32>,
+    /// Pause after every command, in milliseconds. Without it, commands are
+    /// sent as soon as the previous one is answered.
+    pub delay_ms: Option<u64>,
+}
+
+/// Flag shared by REP servers, which stop once it is triggered.
+#[derive(Clone, Default)]
+pub struct Shutdown(Arc<AtomicBool>);
+
+impl Shutdown {
+    pub fn new() -> Shutdown {
+        Shutdown::default()
+    }
+
+    pub fn trigger(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Trigger the shutdown on SIGINT or SIGTERM. Servers finish the request
+    /// they are handling before they stop.
+    pub fn on_signals(&self) -> Result<()> {
+        let shutdown = self.clone();
+        ctrlc::set_handler(move || {
+            info!("shutdown requested");
+            shutdown.trigger();
+        }).chain_err(|| "could not set the signal handler")
+    }
+}
+
+// Triggers the shutdown if the server thread panics, so that the rest of the
+// servers stop as well.
+struct TriggerOnPanic(Shutdown);
+
+impl Drop for TriggerOnPanic {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.trigger();
+        }
+    }
+}
+
+/// Run a REP server for the sensor, until the shutdown is triggered.
+pub fn serve(config: &ServerConfig, shutdown: &Shutdown) -> Result<()> {
+    let server = config
+        .kind
+        .responder(&config.url, &config.path, config.address)?;
+    // Messages of the thread are about the sensor, e.g. in JSON logs.
+    set_sensor(Some(config.kind.name()));
+    info!(
+        "{} REP server listening on {}",
+        config.kind.name(),
+        config.url
+    );
+    let errors = answer_requests(&*server, config, shutdown)?;
+    // Dropping the server closes its socket.
+    drop(server);
+    info!(
+        "{} REP server on {} stopped, {} request(s) failed",
+        config.kind.name(),
+        config.url,
+        errors
+    );
+    Ok(())
+}
+
+// Answers requests until the shutdown is triggered, or until too many
+// consecutive requests failed on the sensor. Returns how many failed.
+fn answer_requests(
+    server: &SensorResponder,
+    config: &ServerConfig,
+    shutdown: &Shutdown,
+) -> Result<u32> {
+    let mut pacer = Pacer::new(config.delay_ms);
+    let mut errors = 0;
+    let mut consecutive_errors = 0;
+    while !shutdown.is_triggered() {
+        let req_str = match server.recv()? {
+            Some(req_str) => req_str,
+            None => continue,
+        };
+        let received = Instant::now();
+        info!("REQ: {}", &req_str);
+        // Every request needs a reply, even if it failed.
+        let evaluated = pacer.pace(&req_str, |s| server.eval(s));
+        let status = Status::of(&evaluated);
+        let call: String = match evaluated {
+            Ok(call) => {
+                consecutive_errors = 0;
+                call
+            }
+            // Mistakes of the client say nothing about the sensor.
+            Err(ref e) if status == Status::UnknownCommand => {
+                errors += 1;
+                warn!("REQ {:?} failed: {}", &req_str, error_chain_message(e));
+                error_reply(e)
+            }
+            Err(e) => {
+                errors += 1;
+                consecutive_errors += 1;
+                error!("REQ {:?} failed: {}", &req_str, error_chain_message(&e));
+                if config.max_errors.map_or(false, |max| consecutive_errors >= max) {
+                    let _reply = server.send(&error_reply(&e))?;
+                    return Err(e).chain_err(|| {
+                        format!("{} consecutive request(s) failed", consecutive_errors)
+                    });
+                }
+                error_reply(&e)
+            }
+        };
+        let _reply = server.send(&call)?;
+        log_exchange(Exchange {
+            request: req_str,
+            reply: call,
+            latency: received.elapsed(),
+        });
+    }
+    if config.sleep_on_exit {
+        match pacer.pace("Sleep", |s| server.eval(s)) {
+            Ok(_) => info!("{} sensor is sleeping", config.kind.name()),
+            Err(e) => warn!("{} sensor could not sleep: {}", config.kind.name(), e),
+        }
+    }
+    Ok(errors)
+}
+
+/// Binds a REP socket to `url`. It times out every `POLL_INTERVAL_MS`
+/// without requests, so that the server can check whether it should stop.
+pub fn bind_responder(url: &str) -> Result<zmq::Socket> {
+    let context = zmq::Context::new();
+    let socket = context
+        .socket(zmq::REP)
+        .chain_err(|| "could not create the REP socket")?;
+    let _timeout = socket
+        .set_rcvtimeo(POLL_INTERVAL_MS)
+        .chain_err(|| "unable to set the receive timeout")?;
+    // Closing the socket on shutdown must not block on undelivered replies.
+    let _linger = socket
+        .set_linger(POLL_INTERVAL_MS)
+        .chain_err(|| "unable to set the socket linger period")?;
+    let _bind = socket
+        .bind(url)
+        .chain_err(|| format!("could not bind to {}", url))?;
+    Ok(socket)
+}
+
+/// Receives the next request, or `None` if none arrived in time. Requests
+/// that are not UTF-8 are answered with an error reply, since the socket
+/// owes every request a reply, and skipped.
+pub fn recv_request(socket: &zmq::Socket) -> Result<Option<String>> {
+    match socket.recv_bytes(0) {
+        Ok(bytes) => match String::from_utf8(bytes) {
+            Ok(request) => Ok(Some(request)),
+            Err(_) => {
+                warn!("received a request that is not valid UTF-8");
+                let reply = format!("{}request is not valid UTF-8", ERROR_REPLY_PREFIX);
+                send_reply(socket, &reply)?;
+                Ok(None)
+            }
+        },
+        // No request in time, or a signal, which may be a shutdown request.
+        Err(zmq::Error::EAGAIN) | Err(zmq::Error::EINTR) => Ok(None),
+        Err(e) => Err(e).chain_err(|| "could not receive the request"),
+    }
+}
+
+/// Sends the reply to the last request received.
+pub fn send_reply(socket: &zmq::Socket, reply: &str) -> Result<()> {
+    socket
+        .send(reply.as_bytes(), 0)
+        .chain_err(|| "could not send the reply")
+}
+
+/// Reply sent to the client when its request failed.
+pub fn error_reply(e: &Error) -> String {
+    format!("{}{}", ERROR_REPLY_PREFIX, error_chain_message(e))
+}
+
+// The error, followed by its causes.
+fn error_chain_message(e: &Error) -> String {
+    e.iter()
+        .map(|e| e.to_string())
+        .collect::<Vec<String>>()
+        .join(": ")
+}
+
+/// Run the REP servers for every sensor concurrently, until the shutdown is
+/// triggered. A server that fails triggers the shutdown of the rest.
+pub fn supervise(configs: Vec<ServerConfig>, shutdown: &Shutdown) -> Result<()> {
+    let mut handles = Vec::new();
+    for config in configs {
+        let name = format!("{}@{}", config.kind.name(), config.url);
+        let server_shutdown = shutdown.clone();
+        let spawned = thread::Builder::new().name(name.clone()).spawn(move || {
+            let _guard = TriggerOnPanic(server_shutdown.clone());
+            let result = serve(&config, &server_shutdown);
+            if let Err(ref e) = result {
+                error!("{} REP server failed: {}", name, e);
+                server_shutdown.trigger();
+            }
+            result
+        });
+        match spawned {
+            Ok(handle) => handles.push(handle),
+            Err(e) => {
+                shutdown.trigger();
+                return Err(e).chain_err(|| "could not start REP server thread");
+            }
+        }
+    }
+
+    let mut failures = 0;
+    for handle in handles {
+        match handle.join() {
+            Ok(Ok(())) => {}
+            Ok(Err(_)) => failures += 1,
+            Err(_) => {
+                error!("REP server thread panicked");
+                failures += 1;
+            }
+        }
+    }
+    if failures > 0 {
+        bail!(ErrorKind::ServersFailed(failures));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use sensors::SensorSpec;
+    use ph;
+
+    // Answers the requests it is given, and triggers the shutdown when there
+    // are none left. `Fail` fails on the sensor.
+    struct FakeResponder {
+        requests: RefCell<Vec<&'static str>>,
+        replies: RefCell<Vec<String>>,
+        shutdown: Shutdown,
+    }
+
+    impl SensorResponder for FakeResponder {
+        fn spec(&self) -> &'static SensorSpec {
+            &ph::SPEC
+        }
+
+        fn recv(&self) -> Result<Option<String>> {
+            let mut requests = self.requests.borrow_mut();
+            if requests.is_empty() {
+                self.shutdown.trigger();
+                return Ok(None);
+            }
+            Ok(Some(requests.remove(0).to_string()))
+        }
+
+        fn send(&self, reply: &str) -> Result<()> {
+            self.replies.borrow_mut().push(reply.to_string());
+            Ok(())
+        }
+
+        fn eval(&self, s: &str) -> Result<String> {
+            match s {
+                "R" => Ok("7.02".to_string()),
+                "Fail" => bail!("no response from sensor"),
+                _ => bail!(ph::SPEC.unrecognized(s)),
+            }
+        }
+    }
+
+    fn answer(requests: Vec<&'static str>) -> (Result<u32>, Vec<String>) {
+        let config = ServerConfig {
+            kind: SensorKind::Ph,
+            path: "/dev/i2c-1".to_string(),
+            address: 99,
+            url: "tcp://127.0.0.1:7778".to_string(),
+            sleep_on_exit: false,
+            max_errors: Some(1),
+            delay_ms: Some(0),
+        };
+        let shutdown = Shutdown::new();
+        let server = FakeResponder {
+            requests: RefCell::new(requests),
+            replies: RefCell::new(Vec::new()),
+            shutdown: shutdown.clone(),
+        };
+        let answered = answer_requests(&server, &config, &shutdown);
+        (answered, server.replies.into_inner())
+    }
+
+    #[test]
+    fn bad_commands_do_not_stop_the_server() {
+        let (answered, replies) = answer(vec!["Zzz", "Cal,mid", "R"]);
+        assert_eq!(answered.unwrap(), 2);
+        assert!(replies[0].starts_with("error: unknown command"));
+        assert!(replies[1].starts_with("error: invalid command"));
+        assert_eq!(replies[2], "7.02");
+
+        let (answered, replies) = answer(vec!["Fail", "R"]);
+        assert!(answered.is_err());
+        assert_eq!(replies, vec!["error: no response from sensor"]);
+    }
+
+    #[test]
+    fn error_replies_include_the_causes() {
+        let e: Error = Error::from("no response from sensor").chain_err(|| "bad REQ eval");
+        assert_eq!(
+            error_reply(&e),
+            "error: bad REQ eval: no response from sensor"
+        );
+    }
+
+    #[test]
+    fn requests_that_are_not_utf8_get_an_error_reply() {
+        let server = bind_responder("tcp://127.0.0.1:*").unwrap();
+        let url = server.get_last_endpoint().unwrap().unwrap();
+        let client = zmq::Context::new().socket(zmq::REQ).unwrap();
+        client.connect(&url).unwrap();
+
+        client.send(&[0xffu8, 0xfe][..], 0).unwrap();
+        assert_eq!(recv_request(&server).unwrap(), None);
+        let reply = client.recv_string(0).unwrap().unwrap();
+        assert!(reply.starts_with(ERROR_REPLY_PREFIX));
+
+        // The server is still in step with the client.
+        client.send("R".as_bytes(), 0).unwrap();
+        assert_eq!(recv_request(&server).unwrap(), Some("R".to_string()));
+        send_reply(&server, "7.02").unwrap();
+        assert_eq!(client.recv_string(0).unwrap().unwrap(), "7.02");
+    }
+}
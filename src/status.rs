@@ -0,0 +1,103 @@
+//! Outcome of the commands sent to sensors, and exit codes of the program.
+use std::fmt;
+
+use super::errors::*;
+
+/// Exit code for errors that have no code of their own.
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit code for invalid command-line arguments.
+pub const EXIT_USAGE: i32 = 2;
+/// Exit code for commands that the sensor doesn't know, or with a wrong syntax.
+pub const EXIT_UNKNOWN_COMMAND: i32 = 3;
+/// Exit code for REP servers that don't reply.
+pub const EXIT_TRANSPORT_FAILED: i32 = 4;
+/// Exit code for sensors that fail to carry out a command.
+pub const EXIT_DEVICE_FAILED: i32 = 5;
+
+/// Outcome of a command, from the least to the most severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    Ok,
+    UnknownCommand,
+    DeviceFailed,
+    TransportFailed,
+}
+
+impl Status {
+    /// Outcome of the result of a command.
+    pub fn of<T>(result: &Result<T>) -> Status {
+        match *result {
+            Ok(_) => Status::Ok,
+            Err(ref e) => match *e.kind() {
+                ErrorKind::UnknownCommand(_) | ErrorKind::InvalidCommand(..) => {
+                    Status::UnknownCommand
+                }
+                ErrorKind::TransportFailed(_) => Status::TransportFailed,
+                _ => Status::DeviceFailed,
+            },
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Status::Ok => "ok",
+            Status::UnknownCommand => "unknown-command",
+            Status::DeviceFailed => "device-failed",
+            Status::TransportFailed => "transport-failed",
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            Status::Ok => 0,
+            Status::UnknownCommand => EXIT_UNKNOWN_COMMAND,
+            Status::DeviceFailed => EXIT_DEVICE_FAILED,
+            Status::TransportFailed => EXIT_TRANSPORT_FAILED,
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Exit code of the program when it stops with the given error.
+pub fn exit_code(e: &Error) -> i32 {
+    match *e.kind() {
+        ErrorKind::InvalidArgs => EXIT_USAGE,
+        ErrorKind::CommandsFailed(_, status) => status.exit_code(),
+        ErrorKind::UnknownCommand(_) | ErrorKind::InvalidCommand(..) => EXIT_UNKNOWN_COMMAND,
+        ErrorKind::TransportFailed(_) => EXIT_TRANSPORT_FAILED,
+        ErrorKind::DeviceFailed(_) => EXIT_DEVICE_FAILED,
+        _ => EXIT_FAILURE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_commands_have_distinct_exit_codes() {
+        let unknown: Result<()> = Err(ErrorKind::UnknownCommand("X".to_string()).into());
+        let transport: Result<()> = Err(ErrorKind::TransportFailed("url".to_string()).into());
+        let device: Result<()> = Err(ErrorKind::DeviceFailed("no data".to_string()).into());
+        assert_eq!(Status::of(&Ok(())), Status::Ok);
+        assert_eq!(Status::of(&unknown), Status::UnknownCommand);
+        assert_eq!(Status::of(&transport), Status::TransportFailed);
+        assert_eq!(Status::of(&device), Status::DeviceFailed);
+
+        let failed = Error::from(ErrorKind::CommandsFailed(2, Status::DeviceFailed));
+        assert_eq!(exit_code(&failed), EXIT_DEVICE_FAILED);
+        assert_eq!(exit_code(&ErrorKind::InvalidArgs.into()), EXIT_USAGE);
+        assert_eq!(exit_code(&"other".into()), EXIT_FAILURE);
+    }
+
+    #[test]
+    fn the_most_severe_status_wins() {
+        let statuses = vec![Status::Ok, Status::TransportFailed, Status::UnknownCommand];
+        assert_eq!(statuses.into_iter().max(), Some(Status::TransportFailed));
+    }
+}
@@ -6,83 +6,52 @@ mod errors {
 pub use self::errors::*;
 
 use benita::ezo::devices::{I2CCommand, I2CResponse};
-use benita::ezo::network::{Endpoint, SocketReply, SocketRequest};
+use benita::ezo::network::SocketRequest;
 use benita::ezo::temperature::device::TemperatureSensor;
-use benita::ezo::temperature::network::{TemperatureRequester, TemperatureResponder};
 use benita::ezo::temperature::network::requests::*;
-use benita::utilities::{create_and_bind_responder, create_and_connect_requester};
+use sensors::{SensorKind, SensorSpec};
 
-// Define TemperatureREQ type
-requester! {
-    TemperatureREQ,
-     "A request (REQ) client for pH servers.",
-     TemperatureRequester,
-     TemperatureRequester,
-     [
-         CalibrationClear,
-         CalibrationState,
-         CalibrationTemperature,
-         DataloggerDisable,
-         DataloggerInterval,
-         DataloggerPeriod,
-         DeviceInformation,
-         Export,
-         ExportInfo,
-         Import,
-         Find,
-         LedOff,
-         LedOn,
-         LedState,
-         MemoryClear,
-         MemoryRecall,
-         MemoryRecallLast,
-         ProtocolLockDisable,
-         ProtocolLockEnable,
-         ProtocolLockState,
-         Reading,
-         ScaleCelsius,
-         ScaleFahrenheit,
-         ScaleKelvin,
-         ScaleState,
-         Status,
-         Sleep
-     ]
-}
-
-// Creates a responder with an explicit list of commands that it handles.
-responder! {
-    TemperatureREP,
-     "A response (REP) server for a Temperature device which handles a list of commands.",
-     TemperatureSensor,
-     TemperatureResponder,
-     TemperatureResponder,
-     [
-         CalibrationClear,
-         CalibrationState,
-         CalibrationTemperature,
-         DataloggerDisable,
-         DataloggerInterval,
-         DataloggerPeriod,
-         DeviceInformation,
-         Export,
-         ExportInfo,
-         Import,
-         Find,
-         LedOff,
-         LedOn,
-         LedState,
-         MemoryClear,
-         MemoryRecall,
-         MemoryRecallLast,
-         ProtocolLockDisable,
-         ProtocolLockEnable,
-         ProtocolLockState,
-         Reading,
-         ScaleCelsius,
-         ScaleFahrenheit,
-         ScaleKelvin,
-         ScaleState,
-         Status,
-         Sleep
-     ]
+// Describes Temperature sensors, and creates their REQ client, REP server, and
+// I2C device handler, which handle the requests below.
+sensor! {
+    TemperatureSensor,
+    SensorKind::Temperature,
+    "temperature",
+    "Commands for temperature",
+    "TEMPERATURE",
+    "°C",
+    TemperatureREQ: "A request (REQ) client for Temperature servers.",
+    TemperatureREP:
+        "A response (REP) server for a Temperature device which handles a list of commands.",
+    TemperatureDevice:
+        "A direct I2C handler for a Temperature device which handles a list of commands.",
+    [
+        CalibrationClear => "Cal,clear",
+        CalibrationState => "Cal,?",
+        CalibrationTemperature => "Cal,<°C>",
+        DataloggerDisable => "D,0",
+        DataloggerInterval => "D,<seconds>",
+        DataloggerPeriod => "D,?",
+        DeviceInformation => "I",
+        Export => "Export",
+        ExportInfo => "Export,?",
+        Import => "Import,<calibration>",
+        Find => "Find",
+        LedOff => "L,0",
+        LedOn => "L,1",
+        LedState => "L,?",
+        MemoryClear => "M,clear",
+        MemoryRecall => "M",
+        MemoryRecallLast => "M,?",
+        ProtocolLockDisable => "Plock,0",
+        ProtocolLockEnable => "Plock,1",
+        ProtocolLockState => "Plock,?",
+        Reading => "R",
+        ScaleCelsius => "S,c",
+        ScaleFahrenheit => "S,f",
+        ScaleKelvin => "S,k",
+        ScaleState => "S,?",
+        Status => "Status",
+        Sleep => "Sleep"
+    ]
 }
@@ -0,0 +1,201 @@
+//! Watch mode, which sends a command to several sensors periodically.
+use std::cmp;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::client::ClientConfig;
+use super::errors::*;
+use super::output::{parse_value, Output, OutputFormat, Record};
+use super::sensors::{SensorKind, SensorRequester};
+use super::server::Shutdown;
+use super::status::Status;
+
+/// How often the watch checks whether it should stop, while waiting.
+const TICK_MS: u64 = 100;
+
+/// Sensors to watch, and how.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WatchConfig {
+    /// Kind of every sensor, and the URL of its REP server.
+    pub sensors: Vec<(SensorKind, String)>,
+    pub command: String,
+    pub interval: Duration,
+    /// Number of rounds, or until the shutdown is triggered if `None`.
+    pub count: Option<u64>,
+    pub format: OutputFormat,
+    pub client: ClientConfig,
+    /// URL of the REP server of a temperature sensor, whose readings are
+    /// used for temperature compensation.
+    pub compensation: Option<String>,
+}
+
+/// Sends the command to every sensor, once per interval. REQ clients whose
+/// REP server doesn't reply connect again on the next round.
+///
+/// With a temperature sensor for compensation, every round starts with its
+/// reading, which is sent to the sensors that compensate for temperature
+/// before their command. The temperature sensor is set to Celsius first, since
+/// the others take the temperature in °C. If the temperature can't be read,
+/// those sensors skip the round.
+pub fn watch(config: &WatchConfig, shutdown: &Shutdown) -> Result<()> {
+    let requesters = config
+        .sensors
+        .iter()
+        .map(|&(kind, ref url)| kind.requester(url, &config.client))
+        .collect::<Result<Vec<Box<SensorRequester>>>>()?;
+    let thermometer = match config.compensation {
+        Some(ref url) => Some(SensorKind::Temperature.requester(url, &config.client)?),
+        None => None,
+    };
+    let mut report = Report::new(config.format);
+
+    let mut start = Instant::now();
+    let mut round = 0;
+    while !shutdown.is_triggered() && config.count.map_or(true, |count| round < count) {
+        let temperature = match thermometer {
+            Some(ref thermometer) => {
+                let (cmd, result) = match thermometer.eval("S,c") {
+                    Ok(_) => ("R", thermometer.eval("R")),
+                    Err(e) => ("S,c", Err(e)),
+                };
+                let temperature = match result {
+                    Ok(ref reply) => parse_value(reply),
+                    Err(_) => None,
+                };
+                report.add(SensorKind::Temperature, cmd, &result)?;
+                Some(temperature)
+            }
+            None => None,
+        };
+        for requester in &requesters {
+            let kind = requester.spec().kind;
+            match temperature {
+                Some(Some(temperature)) if kind.temperature_compensated() => {
+                    let cmd = format!("T,{:.2}", temperature);
+                    let result = requester.eval(&cmd);
+                    if result.is_err() {
+                        report.add(kind, &cmd, &result)?;
+                        continue;
+                    }
+                }
+                Some(None) if kind.temperature_compensated() => {
+                    let reason = "no temperature to compensate for".to_string();
+                    let result = Err(ErrorKind::DeviceFailed(reason).into());
+                    report.add(kind, &config.command, &result)?;
+                    continue;
+                }
+                _ => {}
+            }
+            let result = requester.eval(&config.command);
+            report.add(kind, &config.command, &result)?;
+        }
+        round += 1;
+        start = next_round(start, config.interval, Instant::now());
+        wait_until(start, shutdown);
+    }
+
+    if report.failed > 0 && config.count.is_some() {
+        bail!(ErrorKind::CommandsFailed(report.failed, report.worst));
+    }
+    Ok(())
+}
+
+// Prints the outcomes of the commands, and keeps count of the failed ones.
+struct Report {
+    format: OutputFormat,
+    output: Output,
+    failed: usize,
+    worst: Status,
+}
+
+impl Report {
+    fn new(format: OutputFormat) -> Report {
+        Report {
+            format,
+            output: Output::new(format, "REP"),
+            failed: 0,
+            worst: Status::Ok,
+        }
+    }
+
+    fn add(&mut self, kind: SensorKind, cmd: &str, result: &Result<String>) -> Result<()> {
+        let status = Status::of(result);
+        if status != Status::Ok {
+            self.failed += 1;
+            self.worst = cmp::max(self.worst, status);
+        }
+        let record = Record::new(kind, cmd, result);
+        match self.format {
+            OutputFormat::Text => println!("{}", record.to_row()),
+            _ => self.output.print(&record)?,
+        }
+        Ok(())
+    }
+}
+
+/// Start of the round after the one that started at `start`. Rounds start at
+/// multiples of the interval, and those that a slow round overran are skipped,
+/// instead of running back to back.
+pub fn next_round(start: Instant, interval: Duration, now: Instant) -> Instant {
+    let mut next = start + interval;
+    while next < now {
+        next += interval;
+    }
+    next
+}
+
+/// Sleeps until `deadline`, or until the shutdown is triggered.
+pub fn wait_until(deadline: Instant, shutdown: &Shutdown) {
+    while !shutdown.is_triggered() {
+        let now = Instant::now();
+        if now >= deadline {
+            return;
+        }
+        thread::sleep(cmp::min(deadline - now, Duration::from_millis(TICK_MS)));
+    }
+}
+
+/// Parse an interval, e.g. `5s`, `500ms`, `2m`, or `5` for seconds.
+pub fn parse_interval(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_digit(10)).unwrap_or_else(|| s.len());
+    let (number, unit) = s.split_at(split);
+    let number = number.parse::<u64>().chain_err(|| ErrorKind::InvalidArgs)?;
+    let interval = match unit {
+        "ms" => Duration::from_millis(number),
+        "" | "s" => Duration::from_secs(number),
+        "m" => Duration::from_secs(number * 60),
+        "h" => Duration::from_secs(number * 60 * 60),
+        _ => bail!(ErrorKind::InvalidArgs),
+    };
+    if interval == Duration::from_secs(0) {
+        bail!(ErrorKind::InvalidArgs);
+    }
+    Ok(interval)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intervals_parse_with_units() {
+        assert_eq!(parse_interval("5s").unwrap(), Duration::from_secs(5));
+        assert_eq!(parse_interval("5").unwrap(), Duration::from_secs(5));
+        assert_eq!(parse_interval("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_interval("2m").unwrap(), Duration::from_secs(120));
+        assert!(parse_interval("0s").is_err());
+        assert!(parse_interval("5 fortnights").is_err());
+        assert!(parse_interval("s").is_err());
+    }
+
+    #[test]
+    fn rounds_that_overrun_skip_the_missed_ones() {
+        let start = Instant::now();
+        let interval = Duration::from_secs(5);
+        let on_time = start + Duration::from_secs(1);
+        assert_eq!(next_round(start, interval, on_time), start + interval);
+        let overrun = start + Duration::from_millis(12500);
+        assert_eq!(next_round(start, interval, overrun), start + interval * 3);
+    }
+}